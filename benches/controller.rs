@@ -0,0 +1,41 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use std::hint::black_box;
+use traffic_sim::cars::stress_layout;
+use traffic_sim::config::ControllerConfig;
+use traffic_sim::controller;
+use traffic_sim::sim::{Simulation, MAX_TICK};
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+fn plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plan");
+    let config = ControllerConfig::default();
+    for count in SIZES {
+        let cars = stress_layout(count, 1);
+        group.bench_with_input(BenchmarkId::new("grid", count), &cars, |b, cars| {
+            b.iter(|| controller::plan(black_box(cars), &config))
+        });
+        // The all-pairs reference takes minutes per sample at 10,000 cars
+        if count <= 1_000 {
+            group.bench_with_input(BenchmarkId::new("exhaustive", count), &cars, |b, cars| {
+                b.iter(|| controller::plan_exhaustive(black_box(cars), &config))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn tick(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tick");
+    for count in SIZES {
+        let mut sim = Simulation::with_seed(1);
+        sim.cars = stress_layout(count, 1);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &sim, |b, sim| {
+            b.iter_batched(|| sim.clone(), |mut sim| sim.tick(MAX_TICK), BatchSize::LargeInput)
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, plan, tick);
+criterion_main!(benches);
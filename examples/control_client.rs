@@ -0,0 +1,54 @@
+// Stand-in external controller for the control API: lets one car at a time
+// through the intersection, oldest first. Start the simulator listening and
+// then run the client against it:
+//
+//     cargo run -- --listen 127.0.0.1:7878
+//     cargo run --example control_client --no-default-features -- 127.0.0.1:7878
+use traffic_sim::control::{ControlClient, Message, Request};
+use traffic_sim::world::centre;
+use traffic_sim::ROUTES;
+
+// Cars closer than this to the centre wait for the intersection
+const BOX_RADIUS: f32 = 45.0;
+// Closer than this behind a car from the same approach means a stop
+const GAP: f32 = 15.0;
+const CRUISE: f32 = 15.0;
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+fn main() -> std::io::Result<()> {
+    let addr = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:7878".to_string());
+    let mut client = ControlClient::connect(&addr)?;
+    client.send(&Request::Policy { name: "external".to_string() })?;
+    for route in ROUTES {
+        client.send(&Request::Spawn { route: route.to_string() })?;
+    }
+
+    loop {
+        let cars = match client.receive()? {
+            Message::State { cars, paused: false, .. } => cars,
+            Message::State { .. } => continue,
+            Message::Error { message } => {
+                eprintln!("server: {}", message);
+                continue;
+            }
+        };
+        // Cars come in spawn order, so the first car near the centre is the
+        // one that got there first (or is already leaving)
+        let holder = cars.iter().find(|car| distance(car.cord, centre()) < BOX_RADIUS).map(|car| car.id);
+        for (k, car) in cars.iter().enumerate() {
+            let approach = car.route.split('_').next();
+            let blocked = cars[..k]
+                .iter()
+                .any(|ahead| ahead.route.split('_').next() == approach && distance(ahead.cord, car.cord) < GAP);
+            let waiting = distance(car.cord, centre()) < BOX_RADIUS && holder != Some(car.id);
+            let velocity = if blocked || waiting { 0.0 } else { CRUISE };
+            // People drive the unconnected cars; commands would be ignored
+            if car.connected && velocity != car.target_velocity {
+                client.send(&Request::Command { id: car.id, velocity })?;
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+// Runs the intersection headless through the public API only, as another
+// tool embedding the model would. Builds without the front-end:
+//
+//     cargo run --example embed --no-default-features
+use traffic_sim::{Config, Policy, Simulation, ROUTES};
+
+fn main() {
+    let mut config = Config::default();
+    config.controller.cruise_kmh = 100.0;
+
+    let mut sim = Simulation::with_seed(7);
+    sim.set_config(config);
+    sim.policy = Policy::Smart;
+    for (k, route) in ROUTES.iter().enumerate() {
+        sim.request(route);
+        // Stagger the requests so every car gets a clear entry
+        sim.advance(1.5 + k as f32 * 0.1, |_, _, _| {});
+    }
+
+    while !sim.cars.is_empty() || sim.queued() > 0 {
+        sim.advance(1.0, |sim, _, removed| {
+            for _ in removed {
+                println!("t = {:6.2} s: a car left the world", sim.now());
+            }
+        });
+        for (car, command) in sim.cars.iter().zip(&sim.commands) {
+            let (x, y) = car.get_rect().center();
+            println!("  {:<13} at ({:6.1}, {:6.1}) m, {}", car.direction, x, y, command.describe(&sim.cars));
+        }
+    }
+
+    let stats = &sim.stats;
+    println!("{} cars passed, mean delay {:.2} s, {:.2} MJ", stats.nbr_passed, stats.average_delay, stats.total_energy / 1e6);
+}
@@ -0,0 +1,23 @@
+# Drives the simulation from Python, as in a Jupyter notebook. Build and
+# install the module into the current environment first:
+#
+#     pip install maturin && maturin develop --release
+#     python examples/notebook.py
+import traffic_sim
+
+for cruise in (100, 120, 144):
+    sim = traffic_sim.Simulation({"controller": {"cruise_kmh": cruise}}, seed=1)
+    sim.demand = 20  # random requests per minute
+    sim.step(600)
+    stats = sim.stats()
+    print(f"cruise {cruise:3} km/h: {stats['passed']} passed, "
+          f"delay {stats['average_delay']:.2f} s, energy {stats['total_energy'] / 1e6:.1f} MJ")
+
+# Trajectory of a single car, e.g. for plotting with matplotlib
+sim = traffic_sim.Simulation()
+sim.spawn("up_left")
+trajectory = []
+while sim.step(0.1) == 0:
+    car = sim.cars()[0]
+    trajectory.append((sim.time, car["x"], car["y"], car["velocity"]))
+print(f"up_left crossed in {sim.time:.1f} s over {len(trajectory)} samples")
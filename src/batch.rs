@@ -0,0 +1,261 @@
+use crate::cars::{VehicleId, ROUTES};
+use crate::config::Config;
+use crate::controller::Policy;
+use crate::rng::Rng;
+use crate::sim::{shift_index, Simulation, MAX_TICK};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+// Any car no faster than this for `DEADLOCK_SECONDS` counts as a deadlock,
+// and ends the run: one blocked approach is caught even while the others
+// keep flowing. Long enough for a saturated queue of human drivers, where
+// single cars wait tens of seconds for a gap, not to count.
+const DEADLOCK_VELOCITY: f32 = 0.1;
+const DEADLOCK_SECONDS: f64 = 60.0;
+
+// One headless run: a policy under a demand level (requests per minute over
+// the whole junction, arriving at random) for `duration` simulated seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scenario {
+    pub policy: Policy,
+    pub demand: f64,
+    pub duration: f64,
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunResult {
+    pub scenario: Scenario,
+    pub throughput: f64, // Cars passed per minute
+    pub mean_delay: f64, // Seconds, per passed car
+    pub collisions: u32, // Pairs of cars that came into contact
+    pub deadlocked: bool,
+}
+
+// Every combination of policy and demand, `runs` times each. Run k uses the
+// seed `seed + k` under every policy and demand, so all of them face the
+// same random streams and their differences are not sampling noise.
+pub fn scenarios(policies: &[Policy], demands: &[f64], runs: usize, duration: f64, seed: u64) -> Vec<Scenario> {
+    let mut scenarios = Vec::new();
+    for &policy in policies {
+        for &demand in demands {
+            for k in 0..runs {
+                scenarios.push(Scenario { policy, demand, duration, seed: seed.wrapping_add(k as u64) });
+            }
+        }
+    }
+    scenarios
+}
+
+pub fn run(scenario: &Scenario, config: &Config) -> RunResult {
+    let mut sim = Simulation::with_seed(scenario.seed);
+    sim.policy = scenario.policy;
+    sim.set_config(*config);
+    observe(sim, scenario)
+}
+
+// Runs `sim` to the end of `scenario` with its arrivals, counting new
+// contacts between cars and stopping early on a deadlock
+fn observe(mut sim: Simulation, scenario: &Scenario) -> RunResult {
+    // Arrivals get their own stream so they do not depend on the route draws
+    let mut arrivals = Rng::new(scenario.seed ^ 0xA5A5_A5A5_A5A5_A5A5);
+    let request_chance = scenario.demand / 60.0 * MAX_TICK as f64;
+    let mut contacts: HashSet<(usize, usize)> = HashSet::new();
+    let mut collisions = 0;
+    let mut stopped_since: HashMap<VehicleId, f64> = HashMap::new();
+    let mut deadlocked = false;
+
+    while sim.now() < scenario.duration {
+        if arrivals.next_f64() < request_chance {
+            sim.request_random(&ROUTES);
+        }
+        let removed = sim.tick(MAX_TICK);
+
+        // Pairs that lost a car are dropped, so only new contacts are counted
+        let shift = |i| shift_index(i, &removed);
+        let previous: HashSet<(usize, usize)> =
+            contacts.iter().filter_map(|&(i, j)| Some((shift(i)?, shift(j)?))).collect();
+        contacts = sim.collisions().into_iter().collect();
+        collisions += contacts.difference(&previous).count() as u32;
+
+        let now = sim.now();
+        stopped_since = sim
+            .cars
+            .iter()
+            .filter(|car| car.velocity <= DEADLOCK_VELOCITY)
+            .map(|car| (car.id, stopped_since.get(&car.id).copied().unwrap_or(now)))
+            .collect();
+        if stopped_since.values().any(|since| now - since >= DEADLOCK_SECONDS) {
+            deadlocked = true;
+            break;
+        }
+    }
+
+    let stats = &sim.stats;
+    RunResult {
+        scenario: *scenario,
+        throughput: stats.nbr_passed as f64 / (sim.now() / 60.0),
+        mean_delay: stats.average_delay as f64,
+        collisions,
+        deadlocked,
+    }
+}
+
+// Runs every scenario on `threads` worker threads; results come back in the
+// order of `scenarios`, whatever the thread count
+pub fn run_all(scenarios: &[Scenario], config: &Config, threads: usize) -> Vec<RunResult> {
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, RunResult)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let k = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(scenario) = scenarios.get(k) else {
+                            return done;
+                        };
+                        done.push((k, run(scenario, config)));
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("batch worker panicked")).collect()
+    });
+    results.sort_by_key(|(k, _)| *k);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+// Sample mean with the half-width of its 95% confidence interval
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    pub half_width: f64,
+}
+
+impl Estimate {
+    pub fn of(samples: &[f64]) -> Estimate {
+        let n = samples.len();
+        if n == 0 {
+            return Estimate { mean: f64::NAN, half_width: f64::NAN };
+        }
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        if n == 1 {
+            return Estimate { mean, half_width: f64::INFINITY };
+        }
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        Estimate { mean, half_width: t_95(n - 1) * (variance / n as f64).sqrt() }
+    }
+}
+
+// Two-sided 95% quantile of Student's t distribution. Small batches are
+// common when exploring, and the normal 1.96 would understate their spread.
+fn t_95(degrees: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160, 2.145, 2.131,
+        2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+    ];
+    match degrees {
+        0 => f64::INFINITY,
+        1..=30 => TABLE[degrees - 1],
+        31..=60 => 2.000,
+        61..=120 => 1.980,
+        _ => 1.960,
+    }
+}
+
+// Aggregated results of all the runs of one policy at one demand level
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub policy: Policy,
+    pub demand: f64,
+    pub runs: usize,
+    pub throughput: Estimate,
+    pub mean_delay: Estimate,
+    pub collisions: Estimate,
+    pub deadlocks: Estimate, // Fraction of runs that deadlocked
+}
+
+// One summary per (policy, demand), in order of first appearance
+pub fn summarize(results: &[RunResult]) -> Vec<Summary> {
+    let mut groups: Vec<(Policy, f64)> = Vec::new();
+    for result in results {
+        let key = (result.scenario.policy, result.scenario.demand);
+        if !groups.contains(&key) {
+            groups.push(key);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(policy, demand)| {
+            let runs: Vec<&RunResult> = results
+                .iter()
+                .filter(|r| r.scenario.policy == policy && r.scenario.demand == demand)
+                .collect();
+            let field = |value: fn(&RunResult) -> f64| Estimate::of(&runs.iter().map(|r| value(r)).collect::<Vec<_>>());
+            Summary {
+                policy,
+                demand,
+                runs: runs.len(),
+                throughput: field(|r| r.throughput),
+                // Runs where nobody got through have no delay to report
+                mean_delay: Estimate::of(
+                    &runs.iter().filter(|r| r.throughput > 0.0).map(|r| r.mean_delay).collect::<Vec<_>>(),
+                ),
+                collisions: field(|r| r.collisions as f64),
+                deadlocks: field(|r| if r.deadlocked { 1.0 } else { 0.0 }),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_are_reproducible_and_independent_of_threads() {
+        let scenarios = scenarios(&Policy::ALL, &[20.0, 40.0], 3, 60.0, 40);
+        let config = Config::default();
+        let sequential: Vec<RunResult> = scenarios.iter().map(|scenario| run(scenario, &config)).collect();
+        assert_eq!(run_all(&scenarios, &config, 1), sequential);
+        assert_eq!(run_all(&scenarios, &config, 4), sequential);
+        assert!(sequential.iter().all(|r| r.throughput > 0.0));
+    }
+
+    #[test]
+    fn smart_policy_never_collides_where_uncontrolled_traffic_does() {
+        let results = run_all(&scenarios(&Policy::ALL, &[30.0], 8, 300.0, 1), &Config::default(), 4);
+        let summaries = summarize(&results);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!((summaries[0].policy, summaries[0].runs), (Policy::Smart, 8));
+        assert_eq!(summaries[0].collisions.mean, 0.0, "{:?}", summaries);
+        assert_eq!(summaries[0].deadlocks.mean, 0.0, "{:?}", summaries);
+        assert!(summaries[1].collisions.mean > 1.0, "{:?}", summaries);
+        assert_eq!(summaries[1].deadlocks.mean, 0.0);
+    }
+
+    #[test]
+    fn one_stuck_car_is_a_deadlock_while_others_flow() {
+        let scenario = Scenario { policy: Policy::External, demand: 20.0, duration: 120.0, seed: 0 };
+        let mut sim = Simulation::with_seed(scenario.seed);
+        sim.policy = scenario.policy;
+        // Stopped for good in a corner of the world, off every path
+        assert!(sim.spawn("up_stright", (1.0, 20.0), 0.0));
+        assert!(sim.command(0, 0.0));
+        let result = observe(sim, &scenario);
+        assert!(result.deadlocked);
+        assert!(result.throughput > 0.0, "the other cars kept flowing");
+    }
+
+    #[test]
+    fn estimate_uses_the_t_distribution() {
+        let estimate = Estimate::of(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(estimate.mean, 2.5);
+        // s = sqrt(5/3), t(3) = 3.182
+        let expected = 3.182 * (5.0f64 / 3.0 / 4.0).sqrt();
+        assert!((estimate.half_width - expected).abs() < 1e-9);
+        assert_eq!(Estimate::of(&[7.0; 10]).half_width, 0.0);
+    }
+}
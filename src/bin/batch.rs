@@ -0,0 +1,143 @@
+// Headless Monte Carlo runner: many seeded simulations per policy and demand
+// level, spread over all CPU cores, summarized with 95% confidence intervals.
+//
+//     cargo run --release --bin batch -- --runs 200 --demand 10,20,30
+use std::process::exit;
+use std::thread;
+use traffic_sim::batch::{run_all, scenarios, summarize, RunResult};
+use traffic_sim::config::Config;
+use traffic_sim::controller::Policy;
+
+const USAGE: &str = "usage: batch [--runs N] [--duration SECONDS] [--demand LIST] [--policy LIST]
+             [--seed N] [--threads N] [--config FILE] [--penetration SHARE] [--csv]
+
+  --runs      runs per policy and demand level (default 100)
+  --duration  simulated seconds per run (default 600)
+  --demand    requests per minute, comma separated (default 10,20,30,40)
+  --policy    smart, uncontrolled, comma separated (default both)
+  --seed      seed of the first run (default 1)
+  --threads   worker threads (default: all cores)
+  --config    controller and physics tuning, as in traffic.toml (default: built-in values)
+  --penetration  share of connected AVs, 0 to 1, the rest human-driven (default: from the config)
+  --csv       print every run as CSV instead of the summary table";
+
+struct Options {
+    runs: usize,
+    duration: f64,
+    demands: Vec<f64>,
+    policies: Vec<Policy>,
+    seed: u64,
+    threads: usize,
+    config: Config,
+    csv: bool,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        runs: 100,
+        duration: 600.0,
+        demands: vec![10.0, 20.0, 30.0, 40.0],
+        policies: Policy::ALL.to_vec(),
+        seed: 1,
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        config: Config::default(),
+        csv: false,
+    };
+    // Applied after the loop, so that it wins over a --config given later
+    let mut penetration = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--csv" {
+            options.csv = true;
+            continue;
+        }
+        if flag == "--help" || flag == "-h" {
+            return Err(String::new());
+        }
+        let value = args.next().ok_or(format!("{} needs a value", flag))?;
+        let invalid = format!("invalid value for {}: {}", flag, value);
+        match flag.as_str() {
+            "--runs" => options.runs = value.parse().map_err(|_| invalid.clone())?,
+            "--duration" => options.duration = value.parse().map_err(|_| invalid.clone())?,
+            "--seed" => options.seed = value.parse().map_err(|_| invalid.clone())?,
+            "--threads" => options.threads = value.parse().map_err(|_| invalid.clone())?,
+            "--config" => options.config = Config::load(value.as_ref()).map_err(|err| format!("{}: {}", value, err))?,
+            "--penetration" => penetration = Some(value.parse().map_err(|_| invalid.clone())?),
+            "--demand" => {
+                options.demands = value.split(',').map(|d| d.trim().parse()).collect::<Result<_, _>>().map_err(|_| invalid.clone())?
+            }
+            "--policy" => {
+                options.policies = value
+                    .split(',')
+                    // `external` would leave the cars to a controller that is not there
+                    .map(|name| {
+                        Policy::ALL
+                            .into_iter()
+                            .find(|policy| policy.name() == name.trim())
+                            .ok_or(format!("unknown policy: {} (expected smart or uncontrolled)", name))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            _ => return Err(format!("unknown option: {}", flag)),
+        }
+    }
+    if let Some(penetration) = penetration {
+        options.config.human.penetration = penetration;
+        options.config.validate().map_err(|err| err.to_string())?;
+    }
+    if options.runs == 0 || options.duration <= 0.0 || options.demands.iter().any(|d| *d <= 0.0) {
+        return Err("runs, duration and demand levels must be positive".to_string());
+    }
+    Ok(options)
+}
+
+fn print_csv(results: &[RunResult]) {
+    println!("policy,demand,seed,throughput,mean_delay,collisions,deadlocked");
+    for r in results {
+        println!(
+            "{},{},{},{:.3},{:.3},{},{}",
+            r.scenario.policy.name(), r.scenario.demand, r.scenario.seed,
+            r.throughput, r.mean_delay, r.collisions, r.deadlocked,
+        );
+    }
+}
+
+fn print_summary(results: &[RunResult]) {
+    println!(
+        "{:<13} {:>7} {:>5}  {:>18}  {:>16}  {:>16}  {:>14}",
+        "policy", "demand", "runs", "throughput /min", "delay s", "collisions", "deadlocks %"
+    );
+    for s in summarize(results) {
+        println!(
+            "{:<13} {:>7.1} {:>5}  {:>8.2} ± {:<7.2}  {:>7.2} ± {:<6.2}  {:>7.2} ± {:<6.2}  {:>5.1} ± {:<5.1}",
+            s.policy.name(), s.demand, s.runs,
+            s.throughput.mean, s.throughput.half_width,
+            s.mean_delay.mean, s.mean_delay.half_width,
+            s.collisions.mean, s.collisions.half_width,
+            100.0 * s.deadlocks.mean, 100.0 * s.deadlocks.half_width,
+        );
+    }
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}\n", message);
+            }
+            eprintln!("{}", USAGE);
+            exit(2);
+        }
+    };
+
+    let scenarios = scenarios(&options.policies, &options.demands, options.runs, options.duration, options.seed);
+    eprintln!("{} runs of {} s on {} threads", scenarios.len(), options.duration, options.threads);
+    let results = run_all(&scenarios, &options.config, options.threads);
+
+    if options.csv {
+        print_csv(&results);
+    } else {
+        print_summary(&results);
+    }
+}
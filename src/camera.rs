@@ -0,0 +1,117 @@
+use crate::cars::Car;
+use crate::world::*;
+use macroquad::prelude::*;
+
+const MIN_PIXELS_PER_METRE: f32 = 1.0;
+const MAX_PIXELS_PER_METRE: f32 = 60.0;
+const ZOOM_STEP: f32 = 1.1;
+
+// Interactive view onto the world: mouse-wheel zoom around the cursor,
+// right/middle-drag to pan, and an optional follow mode that keeps the
+// selected car in the middle of the screen.
+//
+// The view is the only thing that depends on the window size. When the
+// window is resized the scale follows it, so the same part of the world stays
+// visible and cars keep their world coordinates.
+pub struct View {
+    pub target: Vec2,
+    pub pixels_per_metre: f32,
+    pub following: bool,
+    drag_from: Option<Vec2>,
+    screen_size: Vec2,
+}
+
+impl View {
+    pub fn new() -> Self {
+        let (cx, cy) = centre();
+        View {
+            target: vec2(cx, cy),
+            pixels_per_metre: fit_scale(),
+            following: false,
+            drag_from: None,
+            screen_size: vec2(screen_width(), screen_height()),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = View::new();
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        Camera2D {
+            target: self.target,
+            zoom: vec2(
+                2.0 * self.pixels_per_metre / screen_width(),
+                -2.0 * self.pixels_per_metre / screen_height(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        self.camera().screen_to_world(point)
+    }
+
+    pub fn update(&mut self, followed: Option<&Car>) {
+        let size = vec2(screen_width(), screen_height());
+        if size != self.screen_size && size.x > 0.0 && size.y > 0.0 {
+            let factor = (size.x / self.screen_size.x).min(size.y / self.screen_size.y);
+            self.pixels_per_metre =
+                (self.pixels_per_metre * factor).clamp(MIN_PIXELS_PER_METRE, MAX_PIXELS_PER_METRE);
+            self.screen_size = size;
+            // The mouse position jumps with the window, so drop any drag in progress
+            self.drag_from = None;
+        }
+
+        let mouse = Vec2::from(mouse_position());
+
+        let wheel = mouse_wheel().1;
+        if wheel != 0.0 {
+            // Keep the world point under the cursor fixed while zooming
+            let before = self.screen_to_world(mouse);
+            let factor = if wheel > 0.0 { ZOOM_STEP } else { 1.0 / ZOOM_STEP };
+            self.pixels_per_metre =
+                (self.pixels_per_metre * factor).clamp(MIN_PIXELS_PER_METRE, MAX_PIXELS_PER_METRE);
+            let after = self.screen_to_world(mouse);
+            self.target += before - after;
+        }
+
+        if is_mouse_button_down(MouseButton::Right) || is_mouse_button_down(MouseButton::Middle) {
+            if let Some(from) = self.drag_from {
+                self.target -= (mouse - from) / self.pixels_per_metre;
+                // Panning by hand takes the camera off the followed car
+                if mouse != from {
+                    self.following = false;
+                }
+            }
+            self.drag_from = Some(mouse);
+        } else {
+            self.drag_from = None;
+        }
+
+        if self.following {
+            match followed {
+                Some(car) => {
+                    self.target = Vec2::from(car.get_rect().center());
+                }
+                None => self.following = false,
+            }
+        }
+
+        self.target.x = self.target.x.clamp(0.0, WORLD_WIDTH);
+        self.target.y = self.target.y.clamp(0.0, WORLD_HEIGHT);
+    }
+}
+
+// Scale at which the default view area fills the current window
+fn fit_scale() -> f32 {
+    (screen_width() / VIEW_WIDTH)
+        .min(screen_height() / VIEW_HEIGHT)
+        .clamp(MIN_PIXELS_PER_METRE, MAX_PIXELS_PER_METRE)
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self::new()
+    }
+}
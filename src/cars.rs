@@ -1,51 +1,190 @@
-use macroquad::prelude::*;
+use crate::config::{CarConfig, Config};
+use crate::drivers::Driver;
+use crate::energy;
+use crate::rng::Rng;
+use crate::spatial::Rect;
+use crate::world;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Every route a car can take: the approach it comes from, then its maneuver
+pub const ROUTES: [&str; 12] = [
+    "up_right", "up_stright", "up_left",
+    "right_right", "right_stright", "right_left",
+    "down_right", "down_stright", "down_left",
+    "left_right", "left_stright", "left_left",
+];
+
+// Entry position and rotation for a route, at the edge of the world
+pub fn spawn_point(direction: &str) -> Option<((f32, f32), f32)> {
+    let (cx, cy) = world::centre();
+    let spawn = match direction {
+        "up_right" => ((cx + 8.5, world::WORLD_HEIGHT - 5.5), 0.0),
+        "up_stright" => ((cx + 4.5, world::WORLD_HEIGHT - 5.5), 0.0),
+        "up_left" => ((cx + 0.5, world::WORLD_HEIGHT - 5.5), 0.0),
+        "right_right" => ((1.0, cy + 7.5), 90.0),
+        "right_stright" => ((1.0, cy + 3.5), 90.0),
+        "right_left" => ((1.0, cy - 0.5), 90.0),
+        "down_right" => ((cx - 11.5, 0.5), 180.0),
+        "down_stright" => ((cx - 7.5, 0.5), 180.0),
+        "down_left" => ((cx - 3.5, 0.5), 180.0),
+        "left_right" => ((world::WORLD_WIDTH - 4.0, cy - 12.5), 270.0),
+        "left_stright" => ((world::WORLD_WIDTH - 4.0, cy - 8.5), 270.0),
+        "left_left" => ((world::WORLD_WIDTH - 4.0, cy - 4.5), 270.0),
+        _ => return None,
+    };
+    Some(spawn)
+}
+
+// Test fixture: a car on `route`, `ahead` metres along its approach from the
+// spawn point (negative for upstream, beyond the world edge)
+#[cfg(test)]
+pub fn approaching(route: &str, ahead: f32) -> Car {
+    let ((x, y), rotation) = spawn_point(route).unwrap();
+    let at = |cord| Car::new(route.to_string(), cord, rotation, 0.0, &Config::default());
+    let (hx, hy) = at((x, y)).current_heading();
+    at((x + hx * ahead, y + hy * ahead))
+}
+
+// Finds the approach lane under a world point and returns its route together
+// with a spawn position and rotation that puts a car centred on that point.
+// Only the part of the lane before the stop line counts.
+pub fn lane_at(point: (f32, f32)) -> Option<(&'static str, (f32, f32), f32)> {
+    let (cx, cy) = world::centre();
+    let stop_line = world::BOX_HALF_WIDTH + world::CAR_LENGTH / 2.0;
+    let half_lane = 2.0;
+    let (px, py) = point;
+
+    for direction in ROUTES {
+        let ((sx, sy), rotation) = spawn_point(direction)?;
+        // Rotation happens around the centre, so the lane centre is the same
+        // whichever way the car faces
+        let lane_x = sx + world::CAR_WIDTH / 2.0;
+        let lane_y = sy + world::CAR_LENGTH / 2.0;
+
+        let on_lane = match direction.split('_').next().unwrap_or("") {
+            "up" => (px - lane_x).abs() < half_lane && py > cy + stop_line,
+            "down" => (px - lane_x).abs() < half_lane && py < cy - stop_line,
+            "right" => (py - lane_y).abs() < half_lane && px < cx - stop_line,
+            "left" => (py - lane_y).abs() < half_lane && px > cx + stop_line,
+            _ => false,
+        };
+        if on_lane {
+            let centre = if rotation == 0.0 || rotation == 180.0 { (lane_x, py) } else { (px, lane_y) };
+            let cord = (centre.0 - world::CAR_WIDTH / 2.0, centre.1 - world::CAR_LENGTH / 2.0);
+            return Some((direction, cord, rotation));
+        }
+    }
+    None
+}
+
+// Stress layout for benchmarks: `count` cars spread evenly over the 12
+// lanes, queued upstream of their spawn point about every 7 m (with some
+// jitter). Lanes extend past the world edge as far as needed, so large counts
+// keep a realistic density instead of piling up.
+pub fn stress_layout(count: usize, seed: u64) -> Vec<Car> {
+    let mut rng = Rng::new(seed);
+    let spacing = 7.0;
+    (0..count)
+        .filter_map(|k| {
+            let direction = ROUTES[k % ROUTES.len()];
+            let ((x, y), rotation) = spawn_point(direction)?;
+            let back = (k / ROUTES.len()) as f32 * spacing + rng.range(0, 100) as f32 / 100.0;
+            let cord = match direction.split('_').next().unwrap_or("") {
+                "up" => (x, y + back),
+                "down" => (x, y - back),
+                "right" => (x - back, y),
+                _ => (x + back, y),
+            };
+            let mut car = Car::new(direction.to_string(), cord, rotation, 0.0, &Config::default());
+            car.id = VehicleId(k as u64);
+            Some(car)
+        })
+        .collect()
+}
+
+// Identity of a car for its whole trip, unlike its index in `cars`, which
+// changes as earlier cars leave. Handed out in spawn order, never reused.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VehicleId(pub u64);
+
+impl fmt::Display for VehicleId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
 
 #[derive(Clone)]
 pub struct Car {
+    pub id: VehicleId,
     pub direction: String,
-    pub width: i32,
-    pub height: i32,
-    pub cord: (f32, f32),
-    pub velocity: f32,        // Replaced 'speed'
+    pub width: f32,           // Metres
+    pub height: f32,          // Metres
+    pub cord: (f32, f32),     // World position in metres
+    pub velocity: f32,        // m/s
     pub target_velocity: f32, // Commanded by the smart intersection
     pub distance: f32,        // Distance left to leave the intersection
     pub time: f32,            // Time left to leave the intersection
+    pub travelled: f32,       // Metres driven since spawning
     pub rotation: f32,
-    pub spawn_time: f64,
+    pub spawn_time: f64,      // Simulation clock time the car was requested
+    pub energy: f64,          // Fuel energy burnt since spawning, in joules
+    pub radar_length: f32,    // Metres ahead the radar sees
+    pub acceleration: f32,    // m/s^2
+    pub deceleration: f32,    // m/s^2
+    pub driver: Option<Driver>, // A person at the wheel, ignoring the controller; none for connected AVs
+    // What the events of `Simulation::tick` were last reported for
+    pub in_box: bool,
+    pub stopped: bool,
+    pub yielding_to: Option<VehicleId>,
 }
 
 impl Car {
-    pub fn new(
-        direction: String,
-        width: i32,
-        height: i32,
-        cord: (f32, f32),
-        rotation: f32,
-    ) -> Self {
+    // Sized, tuned and cruising as `config` says
+    pub fn new(direction: String, cord: (f32, f32), rotation: f32, spawn_time: f64, config: &Config) -> Self {
+        let physics = &config.car;
         Self {
+            id: VehicleId::default(),
             direction,
-            width,
-            height,
+            width: physics.width,
+            height: physics.length,
             cord,
-            velocity: 400.0, // Default to cruising velocity
-            target_velocity: 400.0,
+            velocity: config.controller.cruise(),
+            target_velocity: config.controller.cruise(),
             distance: 0.0,
             time: 0.0,
+            travelled: 0.0,
             rotation,
-            spawn_time: get_time(),
+            spawn_time,
+            energy: 0.0,
+            radar_length: physics.radar_length,
+            acceleration: physics.acceleration,
+            deceleration: physics.deceleration,
+            driver: None,
+            in_box: false,
+            stopped: false,
+            yielding_to: None,
         }
     }
 
+    // Applies tuned physics. The size is left alone: it only applies to new
+    // cars, so that a reload cannot make cars on the road overlap.
+    pub fn configure(&mut self, config: &CarConfig) {
+        self.radar_length = config.radar_length;
+        self.acceleration = config.acceleration;
+        self.deceleration = config.deceleration;
+    }
+
     pub fn current_heading(&self) -> (f32, f32) {
         let (x, y) = self.cord;
-        let cx = screen_width() / 2.0;
-        let cy = screen_height() / 2.0;
+        let (cx, cy) = world::centre();
         let mut dx = 0.0;
         let mut dy = 0.0;
 
         match self.direction.as_str() {
             "up_right" => {
-                if y < cy + 80.0 {
+                if y < cy + 8.0 {
                     dx = 1.0;
                 } else {
                     dy = -1.0;
@@ -55,14 +194,14 @@ impl Car {
                 dy = -1.0;
             }
             "up_left" => {
-                if y < cy - 40.0 {
+                if y < cy - 4.0 {
                     dx = -1.0;
                 } else {
                     dy = -1.0;
                 }
             }
             "right_right" => {
-                if x < cx - 115.0 {
+                if x < cx - 11.5 {
                     dx = 1.0;
                 } else {
                     dy = 1.0;
@@ -72,14 +211,14 @@ impl Car {
                 dx = 1.0;
             }
             "right_left" => {
-                if x < cx + 5.0 {
+                if x < cx + 0.5 {
                     dx = 1.0;
                 } else {
                     dy = -1.0;
                 }
             }
             "down_right" => {
-                if y < cy - 125.0 {
+                if y < cy - 12.5 {
                     dy = 1.0;
                 } else {
                     dx = -1.0;
@@ -89,14 +228,14 @@ impl Car {
                 dy = 1.0;
             }
             "down_left" => {
-                if y < cy - 5.0 {
+                if y < cy - 0.5 {
                     dy = 1.0;
                 } else {
                     dx = 1.0;
                 }
             }
             "left_right" => {
-                if x > cx + 85.0 {
+                if x > cx + 8.5 {
                     dx = -1.0;
                 } else {
                     dy = -1.0;
@@ -106,7 +245,7 @@ impl Car {
                 dx = -1.0;
             }
             "left_left" => {
-                if x > cx - 35.0 {
+                if x > cx - 3.5 {
                     dx = -1.0;
                 } else {
                     dy = 1.0;
@@ -120,14 +259,14 @@ impl Car {
 
 
     pub fn get_rect(&self) -> Rect {
-        let w = self.width as f32;
-        let h = self.height as f32;
+        let w = self.width;
+        let h = self.height;
 
         // Macroquad rotates textures around their center point
         let cx = self.cord.0 + w / 2.0;
         let cy = self.cord.1 + h / 2.0;
 
-        let (dx, dy) = self.current_heading();
+        let (dx, _) = self.current_heading();
 
         if dx.abs() > 0.1 {
             // Moving horizontally: visually, width and height are swapped
@@ -138,11 +277,19 @@ impl Car {
         }
     }
 
+    pub fn connected(&self) -> bool {
+        self.driver.is_none()
+    }
+
     pub fn get_radar(&self) -> Rect {
+        self.look_ahead(self.radar_length)
+    }
+
+    // The `radar_len` metres in front of the car
+    pub fn look_ahead(&self, radar_len: f32) -> Rect {
         // Base the radar directly off the accurately rotated physical bounding box
         let rect = self.get_rect();
         let (dx, dy) = self.current_heading();
-        let radar_len = 100.0;
 
         if dx > 0.1 {
             // Moving right: radar projects from the right edge
@@ -162,8 +309,9 @@ impl Car {
     }
 
     pub fn update(&mut self, dt: f32) {
-        let accel = 500.0;
-        let decel = 1500.0; // Very strong brakes for emergency AV stopping
+        let accel = self.acceleration;
+        let decel = self.deceleration; // Very strong brakes for emergency AV stopping
+        let previous_velocity = self.velocity;
 
         if self.velocity < self.target_velocity {
             self.velocity += accel * dt;
@@ -177,12 +325,19 @@ impl Car {
             }
         }
 
+        if dt > 0.0 {
+            let accel_now = (self.velocity - previous_velocity) / dt;
+            self.energy += (energy::fuel_power(self.velocity, accel_now) * dt) as f64;
+        }
+
         let step = self.velocity * dt;
+        self.travelled += step;
+        let (cx, cy) = world::centre();
         let (mut x, mut y) = self.cord;
 
         match self.direction.as_str() {
             "up_right" => {
-                if y < screen_height() / 2.0 + 80.0 {
+                if y < cy + 8.0 {
                     x += step;
                     self.rotation = 90.0;
                 } else {
@@ -194,7 +349,7 @@ impl Car {
                 y -= step;
             }
             "up_left" => {
-                if y < screen_height() / 2.0 - 40.0 {
+                if y < cy - 4.0 {
                     x -= step;
                     self.rotation = 270.0;
                 } else {
@@ -202,7 +357,7 @@ impl Car {
                 }
             }
             "right_right" => {
-                if x < screen_width() / 2.0 - 115.0 {
+                if x < cx - 11.5 {
                     x += step;
                 } else {
                     y += step;
@@ -213,7 +368,7 @@ impl Car {
                 x += step;
             }
             "right_left" => {
-                if x < screen_width() / 2.0 + 5.0 {
+                if x < cx + 0.5 {
                     x += step;
                 } else {
                     y -= step;
@@ -221,7 +376,7 @@ impl Car {
                 }
             }
             "down_right" => {
-                if y < screen_height() / 2.0 - 125.0 {
+                if y < cy - 12.5 {
                     y += step;
                 } else {
                     x -= step;
@@ -232,7 +387,7 @@ impl Car {
                 y += step;
             }
             "down_left" => {
-                if y < screen_height() / 2.0 - 5.0 {
+                if y < cy - 0.5 {
                     y += step;
                 } else {
                     x += step;
@@ -240,7 +395,7 @@ impl Car {
                 }
             }
             "left_right" => {
-                if x > screen_width() / 2.0 + 85.0 {
+                if x > cx + 8.5 {
                     x -= step;
                 } else {
                     y -= step;
@@ -251,7 +406,7 @@ impl Car {
                 x -= step;
             }
             "left_left" => {
-                if x > screen_width() / 2.0 - 35.0 {
+                if x > cx - 3.5 {
                     x -= step;
                 } else {
                     y += step;
@@ -265,10 +420,10 @@ impl Car {
 
         // Physics updates: Calculate remaining distance and time
         match self.direction.split('_').next().unwrap_or("") {
-            "up" => self.distance = y + 50.0,
-            "down" => self.distance = screen_height() - y + 50.0,
-            "left" => self.distance = x + 50.0,
-            "right" => self.distance = screen_width() - x + 50.0,
+            "up" => self.distance = y + 5.0,
+            "down" => self.distance = world::WORLD_HEIGHT - y + 5.0,
+            "left" => self.distance = x + 5.0,
+            "right" => self.distance = world::WORLD_WIDTH - x + 5.0,
             _ => self.distance = 0.0,
         }
 
@@ -279,3 +434,109 @@ impl Car {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Where the car must leave the world, and the rotation it must have then
+    fn expected_exit(direction: &str) -> (&'static str, f32) {
+        match direction {
+            "up_stright" | "right_left" | "left_right" => ("north", 0.0),
+            "right_stright" | "up_right" | "down_left" => ("east", 90.0),
+            "down_stright" | "right_right" | "left_left" => ("south", 180.0),
+            "left_stright" | "up_left" | "down_right" => ("west", 270.0),
+            _ => panic!("unknown route {}", direction),
+        }
+    }
+
+    fn edge(cord: (f32, f32)) -> &'static str {
+        let (x, y) = cord;
+        if y < 0.0 {
+            "north"
+        } else if y > world::WORLD_HEIGHT {
+            "south"
+        } else if x < 0.0 {
+            "west"
+        } else if x > world::WORLD_WIDTH {
+            "east"
+        } else {
+            "inside"
+        }
+    }
+
+    #[test]
+    fn every_route_exits_on_its_edge_with_its_rotation() {
+        for direction in ROUTES {
+            let (cord, rotation) = spawn_point(direction).unwrap();
+            let mut car = Car::new(direction.to_string(), cord, rotation, 0.0, &Config::default());
+
+            let mut ticks = 0;
+            while edge(car.cord) == "inside" {
+                car.update(1.0 / 60.0);
+                ticks += 1;
+                assert!(ticks < 60 * 60, "{} never left the world", direction);
+            }
+
+            let (expected_edge, expected_rotation) = expected_exit(direction);
+            assert_eq!(edge(car.cord), expected_edge, "{} left through the wrong edge", direction);
+            assert_eq!(car.rotation, expected_rotation, "{} has the wrong rotation", direction);
+        }
+    }
+
+    #[test]
+    fn heading_matches_movement() {
+        for direction in ROUTES {
+            let (cord, rotation) = spawn_point(direction).unwrap();
+            let mut car = Car::new(direction.to_string(), cord, rotation, 0.0, &Config::default());
+            for _ in 0..600 {
+                let (dx, dy) = car.current_heading();
+                let before = car.cord;
+                car.update(1.0 / 60.0);
+                let moved = (car.cord.0 - before.0, car.cord.1 - before.1);
+                assert!(moved.0 * dx >= 0.0 && moved.1 * dy >= 0.0, "{} moved against its heading", direction);
+            }
+        }
+    }
+
+    #[test]
+    fn new_cars_cruise_and_are_sized_as_configured() {
+        let mut config = Config::default();
+        config.controller.cruise_kmh = 60.0;
+        config.car.length = 5.0;
+        let (cord, rotation) = spawn_point("up_left").unwrap();
+        let car = Car::new("up_left".to_string(), cord, rotation, 0.0, &config);
+        assert_eq!((car.velocity, car.target_velocity), (world::kmh(60.0), world::kmh(60.0)));
+        assert_eq!((car.width, car.height), (config.car.width, 5.0));
+    }
+
+    #[test]
+    fn velocity_approaches_target_within_limits() {
+        let (cord, rotation) = spawn_point("up_stright").unwrap();
+        let mut car = Car::new("up_stright".to_string(), cord, rotation, 0.0, &Config::default());
+        car.target_velocity = 0.0;
+        car.update(0.1);
+        assert!(car.velocity > 0.0, "braking is limited, not instant");
+        for _ in 0..10 {
+            car.update(0.1);
+        }
+        assert_eq!(car.velocity, 0.0);
+        assert!(car.time.is_infinite());
+
+        car.target_velocity = 10.0;
+        for _ in 0..10 {
+            car.update(0.1);
+        }
+        assert_eq!(car.velocity, 10.0, "acceleration never overshoots the target");
+    }
+
+    #[test]
+    fn spawn_lanes_round_trip() {
+        for direction in ROUTES {
+            let ((x, y), _) = spawn_point(direction).unwrap();
+            let centre = (x + world::CAR_WIDTH / 2.0, y + world::CAR_LENGTH / 2.0);
+            let (found, _, _) = lane_at(centre).unwrap();
+            assert_eq!(found, direction);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::cars::Car;
+use crate::world::*;
+use crate::stats::Stats;
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+
+const SAMPLE_INTERVAL: f64 = 1.0; // Seconds of simulation between samples
+const WINDOW_MINUTES: usize = 5;
+const CAPACITY: usize = WINDOW_MINUTES * 60;
+
+// Slower than this outside the box counts as queued
+const QUEUE_VELOCITY: f32 = 2.0;
+
+const APPROACHES: [&str; 4] = ["up", "right", "down", "left"];
+const APPROACH_COLORS: [Color; 4] = [SKYBLUE, ORANGE, PINK, LIME];
+
+#[derive(Clone, Copy, Default)]
+struct Sample {
+    active: usize,
+    passed: u32,
+    throughput: f32, // Cars passed over the last minute
+    mean_velocity: f32,
+    queues: [usize; 4],
+}
+
+// Reads one value out of a sample
+type Series = fn(&Sample) -> f32;
+
+// Number of cars queued on each approach, in `APPROACHES` order
+pub fn queue_lengths(cars: &[Car]) -> [usize; 4] {
+    let (cx, cy) = centre();
+    let mut queues = [0; 4];
+    for car in cars {
+        let (x, y) = car.get_rect().center();
+        let outside_box = (x - cx).abs() > BOX_HALF_WIDTH || (y - cy).abs() > BOX_HALF_WIDTH;
+        if car.velocity >= QUEUE_VELOCITY || !outside_box {
+            continue;
+        }
+        let approach = car.direction.split('_').next().unwrap_or("");
+        if let Some(k) = APPROACHES.iter().position(|a| *a == approach) {
+            queues[k] += 1;
+        }
+    }
+    queues
+}
+
+// Rolling history of the simulation over the last `WINDOW_MINUTES`,
+// sampled once per `SAMPLE_INTERVAL` on the simulation clock.
+pub struct History {
+    samples: VecDeque<Sample>,
+    next_sample: f64,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History { samples: VecDeque::with_capacity(CAPACITY), next_sample: SAMPLE_INTERVAL }
+    }
+
+    pub fn record(&mut self, now: f64, cars: &[Car], stats: &Stats) {
+        if now < self.next_sample {
+            return;
+        }
+        self.next_sample += SAMPLE_INTERVAL;
+
+        let minute_ago = self.samples.len().checked_sub(60).and_then(|i| self.samples.get(i));
+        let passed_minute_ago = minute_ago.map_or(0, |s| s.passed);
+        let mean_velocity = if cars.is_empty() {
+            0.0
+        } else {
+            cars.iter().map(|car| car.velocity).sum::<f32>() / cars.len() as f32
+        };
+
+        if self.samples.len() == CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            active: cars.len(),
+            passed: stats.nbr_passed,
+            // Statistics restart when the tuning changes, so the count can drop
+            throughput: stats.nbr_passed.saturating_sub(passed_minute_ago) as f32,
+            mean_velocity: to_kmh(mean_velocity),
+            queues: queue_lengths(cars),
+        });
+    }
+
+    pub fn draw(&self) {
+        let x = 10.0;
+        let w = 300.0;
+        let h = 90.0;
+        let mut y = 40.0;
+        draw_rectangle(x - 5.0, y - 5.0, w + 10.0, 5.0 * (h + 30.0) + 5.0, Color::from_rgba(0, 0, 0, 180));
+
+        let series: [(&str, Series, &str); 4] = [
+            ("Active cars", |s| s.active as f32, ""),
+            ("Passed cars", |s| s.passed as f32, ""),
+            ("Throughput", |s| s.throughput, " /min"),
+            ("Mean velocity", |s| s.mean_velocity, " km/h"),
+        ];
+        for (label, value, unit) in series {
+            let current = self.samples.back().map_or(0.0, value);
+            draw_text(&format!("{}: {:.0}{}", label, current, unit), x, y + 14.0, 18.0, WHITE);
+            self.draw_chart(x, y + 20.0, w, h, &[(value, WHITE)]);
+            y += h + 30.0;
+        }
+
+        let queues = self.samples.back().map_or([0; 4], |s| s.queues);
+        draw_text(
+            &format!("Queues  up {}  right {}  down {}  left {}", queues[0], queues[1], queues[2], queues[3]),
+            x, y + 14.0, 18.0, WHITE,
+        );
+        let lines: [(Series, Color); 4] = [
+            (|s| s.queues[0] as f32, APPROACH_COLORS[0]),
+            (|s| s.queues[1] as f32, APPROACH_COLORS[1]),
+            (|s| s.queues[2] as f32, APPROACH_COLORS[2]),
+            (|s| s.queues[3] as f32, APPROACH_COLORS[3]),
+        ];
+        self.draw_chart(x, y + 20.0, w, h, &lines);
+    }
+
+    // Line chart of the whole window; the y axis starts at zero and is
+    // scaled to the largest value of all the series drawn together
+    fn draw_chart(&self, x: f32, y: f32, w: f32, h: f32, lines: &[(Series, Color)]) {
+        draw_rectangle_lines(x, y, w, h, 1.0, GRAY);
+
+        let max = lines
+            .iter()
+            .flat_map(|(value, _)| self.samples.iter().map(value))
+            .fold(1.0_f32, f32::max);
+        let step = w / (CAPACITY - 1) as f32;
+        let offset = (CAPACITY - self.samples.len()) as f32 * step;
+
+        for (value, color) in lines {
+            let points: Vec<Vec2> = self
+                .samples
+                .iter()
+                .enumerate()
+                .map(|(k, s)| vec2(x + offset + k as f32 * step, y + h - value(s) / max * h))
+                .collect();
+            for pair in points.windows(2) {
+                draw_line(pair[0].x, pair[0].y, pair[1].x, pair[1].y, 1.5, *color);
+            }
+        }
+        draw_text(&format!("{:.0}", max), x + w - 30.0, y + 14.0, 16.0, GRAY);
+        draw_text(&format!("-{} min", WINDOW_MINUTES), x + 2.0, y + h - 4.0, 16.0, GRAY);
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cars::approaching;
+
+    #[test]
+    fn stopped_cars_queue_on_their_approach_until_the_box() {
+        // Centred 1 m before and 1 m past the box edge, coming from the left
+        let at_box = |offset: f32| {
+            let car = approaching("right_stright", 0.0);
+            let (x, _) = car.get_rect().center();
+            let mut car = approaching("right_stright", centre().0 - BOX_HALF_WIDTH - x + offset);
+            car.velocity = 0.0;
+            car
+        };
+        assert_eq!(queue_lengths(&[at_box(-1.0)]), [0, 1, 0, 0]);
+        assert_eq!(queue_lengths(&[at_box(1.0)]), [0; 4]);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::cyclists::CYCLIST_WIDTH;
+use crate::world::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Every tunable constant of the controller and the vehicle physics, loaded
+// from a TOML file (see `traffic.toml`). Missing keys keep their defaults,
+// which are the values the simulation was tuned with; unknown keys are
+// rejected so that a typo does not silently leave a value at its default.
+// Velocities are written in km/h, everything else in metres and seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub controller: ControllerConfig,
+    pub car: CarConfig,
+    pub spawn: SpawnConfig,
+    pub pedestrian: PedestrianConfig,
+    pub bike: BikeConfig,
+    pub human: HumanConfig,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControllerConfig {
+    pub cruise_kmh: f32,
+    pub adjust_kmh: f32,
+    pub crawl_kmh: f32,
+    pub safety_distance: f32, // Closer than this behind another car means a full stop
+    pub yield_radius: f32,    // Cars inside have priority over cars outside
+    pub stop_radius: f32,     // Inside this, yielding means a full stop
+    pub tie_margin: f32,      // Closer than this to the centre counts as a tie
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CarConfig {
+    pub width: f32,
+    pub length: f32,
+    pub radar_length: f32,
+    pub acceleration: f32, // m/s^2
+    pub deceleration: f32, // m/s^2
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnConfig {
+    pub gap: f32, // Free distance needed around a lane entry before a car enters
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PedestrianConfig {
+    pub speed: f32,      // m/s
+    pub gap_margin: f32, // Seconds to spare before straight traffic reaches a crosser
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BikeConfig {
+    pub lane_width: f32, // Bike lane beside the outer lane, on each side of the road
+    pub speed_kmh: f32,
+    pub gap_margin: f32, // Seconds to spare before traffic reaches a crossing cyclist
+}
+
+// Mixed traffic: cars not connected to the intersection are driven by people
+// (see `drivers`). The values are for an average driver; each driver's
+// profile and a random spread scale them.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HumanConfig {
+    pub penetration: f32,    // Share of new cars that are connected AVs, 0 to 1
+    pub cautious_share: f32, // Share of human drivers who are cautious rather than aggressive
+    pub reaction_time: f32,  // Seconds from seeing something to acting on it
+    pub critical_gap: f32,   // Seconds of gap to cross traffic needed to enter the box
+    pub speed_kmh: f32,
+}
+
+impl HumanConfig {
+    pub fn speed(&self) -> f32 {
+        kmh(self.speed_kmh)
+    }
+}
+
+impl BikeConfig {
+    pub fn speed(&self) -> f32 {
+        kmh(self.speed_kmh)
+    }
+}
+
+impl ControllerConfig {
+    pub fn cruise(&self) -> f32 {
+        kmh(self.cruise_kmh)
+    }
+
+    pub fn adjust(&self) -> f32 {
+        kmh(self.adjust_kmh)
+    }
+
+    pub fn crawl(&self) -> f32 {
+        kmh(self.crawl_kmh)
+    }
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        ControllerConfig {
+            cruise_kmh: 144.0,
+            adjust_kmh: 90.0,
+            crawl_kmh: 36.0,
+            safety_distance: 15.0,
+            yield_radius: 25.0,
+            stop_radius: 20.0,
+            tie_margin: 4.0,
+        }
+    }
+}
+
+impl Default for CarConfig {
+    fn default() -> Self {
+        CarConfig { width: CAR_WIDTH, length: CAR_LENGTH, radar_length: 10.0, acceleration: 50.0, deceleration: 150.0 }
+    }
+}
+
+impl Default for SpawnConfig {
+    fn default() -> Self {
+        SpawnConfig { gap: 22.0 }
+    }
+}
+
+impl Default for PedestrianConfig {
+    fn default() -> Self {
+        PedestrianConfig { speed: 1.4, gap_margin: 2.0 }
+    }
+}
+
+impl Default for BikeConfig {
+    fn default() -> Self {
+        BikeConfig { lane_width: 1.5, speed_kmh: 18.0, gap_margin: 1.5 }
+    }
+}
+
+impl Default for HumanConfig {
+    fn default() -> Self {
+        HumanConfig { penetration: 1.0, cautious_share: 0.5, reaction_time: 1.0, critical_gap: 3.0, speed_kmh: 72.0 }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "cannot read config: {}", err),
+            ConfigError::Parse(err) => write!(f, "cannot parse config: {}", err),
+            ConfigError::Invalid(reason) => write!(f, "invalid config: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn new() -> Self {
+        Config {
+            controller: ControllerConfig::default(),
+            car: CarConfig::default(),
+            spawn: SpawnConfig::default(),
+            pedestrian: PedestrianConfig::default(),
+            bike: BikeConfig::default(),
+            human: HumanConfig::default(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let config: Config = toml::from_str(text).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        Config::parse(&std::fs::read_to_string(path).map_err(ConfigError::Io)?)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("config always serializes")
+    }
+
+    // Rejects values the controller cannot work with, such as speed bands
+    // out of order or radii that would let a car stop inside the box before
+    // it has priority
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let c = &self.controller;
+        let car = &self.car;
+        let invalid = |reason: &str| Err(ConfigError::Invalid(reason.to_string()));
+        let values = [
+            c.cruise_kmh, c.adjust_kmh, c.crawl_kmh, c.safety_distance, c.yield_radius, c.stop_radius, c.tie_margin,
+            car.width, car.length, car.radar_length, car.acceleration, car.deceleration, self.spawn.gap,
+            self.pedestrian.speed, self.pedestrian.gap_margin, self.bike.lane_width, self.bike.speed_kmh,
+            self.bike.gap_margin, self.human.penetration, self.human.cautious_share, self.human.reaction_time,
+            self.human.critical_gap, self.human.speed_kmh,
+        ];
+        if values.iter().any(|v| !v.is_finite()) {
+            return invalid("values must be finite numbers");
+        }
+        if !(c.cruise_kmh >= c.adjust_kmh && c.adjust_kmh >= c.crawl_kmh && c.crawl_kmh > 0.0) {
+            return invalid("velocities must satisfy cruise >= adjust >= crawl > 0");
+        }
+        if c.safety_distance <= 0.0 || c.tie_margin < 0.0 {
+            return invalid("safety_distance must be positive and tie_margin not negative");
+        }
+        if !(0.0 < c.stop_radius && c.stop_radius <= c.yield_radius) {
+            return invalid("radii must satisfy 0 < stop_radius <= yield_radius");
+        }
+        if car.width <= 0.0 || car.length <= 0.0 || car.radar_length <= 0.0 {
+            return invalid("car width, length and radar_length must be positive");
+        }
+        if car.acceleration <= 0.0 || car.deceleration <= 0.0 {
+            return invalid("acceleration and deceleration must be positive");
+        }
+        if self.spawn.gap < car.length {
+            return invalid("spawn gap must be at least one car length");
+        }
+        if self.pedestrian.speed <= 0.0 || self.pedestrian.gap_margin < 0.0 {
+            return invalid("pedestrian speed must be positive and gap_margin not negative");
+        }
+        if self.bike.lane_width <= CYCLIST_WIDTH || self.bike.speed_kmh <= 0.0 || self.bike.gap_margin < 0.0 {
+            return invalid("bike lane_width must fit a cyclist, speed positive and gap_margin not negative");
+        }
+        let human = &self.human;
+        if !(0.0..=1.0).contains(&human.penetration) || !(0.0..=1.0).contains(&human.cautious_share) {
+            return invalid("human penetration and cautious_share must be between 0 and 1");
+        }
+        if human.reaction_time < 0.0 || human.critical_gap < 0.0 || human.speed_kmh <= 0.0 {
+            return invalid("human reaction_time and critical_gap must not be negative, speed must be positive");
+        }
+        Ok(())
+    }
+}
+
+// Watches a config file for changes by polling its modification time, so
+// tuning sessions pick up edits without restarting
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    // The file as it is now counts as seen; load it separately at startup
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+        ConfigWatcher { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // The reloaded config after the file changed, or the reason it could not
+    // be used. Nothing when the file is unchanged (or missing).
+    pub fn poll(&mut self) -> Option<Result<Config, ConfigError>> {
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Config::load(&self.path))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_file_matches_the_defaults() {
+        let text = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/traffic.toml")).unwrap();
+        assert_eq!(Config::parse(&text).unwrap(), Config::default());
+        assert_eq!(Config::parse(&Config::default().to_toml()).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let config = Config::parse("[controller]\ncruise_kmh = 100.0\n").unwrap();
+        assert_eq!(config.controller.cruise_kmh, 100.0);
+        assert_eq!(config.controller.adjust_kmh, ControllerConfig::default().adjust_kmh);
+        assert_eq!(config.car, CarConfig::default());
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        assert!(matches!(Config::parse("[controller]\ncruse_kmh = 100.0\n"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("[car]\nwidth = \"wide\"\n"), Err(ConfigError::Parse(_))));
+        assert!(matches!(Config::parse("[controller]\ncrawl_kmh = 200.0\n"), Err(ConfigError::Invalid(_))));
+        assert!(matches!(Config::parse("[controller]\nstop_radius = 30.0\n"), Err(ConfigError::Invalid(_))));
+        assert!(matches!(Config::parse("[car]\ndeceleration = 0.0\n"), Err(ConfigError::Invalid(_))));
+        assert!(matches!(Config::parse("[human]\npenetration = 1.5\n"), Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn watcher_reports_each_change_once() {
+        let path = std::env::temp_dir().join(format!("traffic_sim_watch_{}.toml", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let mut watcher = ConfigWatcher::new(&path);
+        assert!(watcher.poll().is_none());
+
+        // Make sure the modification time moves even on coarse filesystems
+        let later = SystemTime::now() + std::time::Duration::from_secs(5);
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        std::fs::write(&path, "[spawn]\ngap = 30.0\n").unwrap();
+        file.set_modified(later).unwrap();
+        assert_eq!(watcher.poll().unwrap().unwrap().spawn.gap, 30.0);
+        assert!(watcher.poll().is_none());
+
+        std::fs::write(&path, "[spawn]\ngap = 1.0\n").unwrap();
+        file.set_modified(later + std::time::Duration::from_secs(5)).unwrap();
+        assert!(matches!(watcher.poll(), Some(Err(ConfigError::Invalid(_)))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,415 @@
+use crate::cars::{VehicleId, ROUTES};
+use crate::controller::Policy;
+use crate::events::Event;
+use crate::sim::Simulation;
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+// Local control API: external processes (a C++ planner, a Python prototype)
+// connect over TCP and exchange newline-delimited JSON. The server streams a
+// `State` after every tick and accepts `Request`s, e.g.
+//
+//     {"type":"policy","name":"external"}
+//     {"type":"command","id":3,"velocity":4.5}
+//     {"type":"spawn","route":"up_left"}
+//     {"type":"pause"}  {"type":"resume"}  {"type":"reset","seed":7}
+//
+// Car ids are `VehicleId`s, stable for a car's whole trip. Each `State`
+// also carries the vehicle events of its tick. Malformed requests get an
+// `{"type":"error"}` line.
+
+// A client reading no faster than this falls behind and is dropped
+const MAX_PENDING_BYTES: usize = 8 << 20;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    // Target velocity (m/s) for a car; it lasts under the `external` policy
+    Command { id: VehicleId, velocity: f32 },
+    Spawn { route: String },
+    Pause,
+    Resume,
+    Reset { seed: Option<u64> },
+    Policy { name: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CarState {
+    pub id: VehicleId,
+    pub route: String,
+    pub cord: (f32, f32),
+    pub velocity: f32,
+    pub target_velocity: f32,
+    pub connected: bool, // False for human-driven cars; commands to them are refused
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    State {
+        tick: u64,
+        time: f64,
+        paused: bool,
+        policy: String,
+        queued: usize,
+        cars: Vec<CarState>,
+        events: Vec<Event>,
+    },
+    Error { message: String },
+}
+
+impl Message {
+    // `events` are the ones to report with it, if any
+    pub fn state(sim: &Simulation, tick: u64, paused: bool, events: &[Event]) -> Message {
+        let cars = sim
+            .cars
+            .iter()
+            .zip(&sim.commands)
+            .map(|(car, command)| CarState {
+                id: car.id,
+                route: car.direction.clone(),
+                cord: car.cord,
+                velocity: car.velocity,
+                target_velocity: command.velocity,
+                connected: car.connected(),
+            })
+            .collect();
+        Message::State {
+            tick,
+            time: sim.now(),
+            paused,
+            policy: sim.policy.name().to_string(),
+            queued: sim.queued(),
+            cars,
+            events: events.to_vec(),
+        }
+    }
+}
+
+struct Client {
+    stream: TcpStream,
+    incoming: Vec<u8>, // Bytes of a request line not complete yet
+    outgoing: Vec<u8>, // Bytes the socket did not take yet
+    closed: bool,
+}
+
+impl Client {
+    fn send(&mut self, message: &Message) {
+        let mut line = serde_json::to_vec(message).expect("messages always serialize");
+        line.push(b'\n');
+        self.outgoing.extend_from_slice(&line);
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        while !self.outgoing.is_empty() && !self.closed {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+        if self.outgoing.len() > MAX_PENDING_BYTES {
+            self.closed = true;
+        }
+    }
+
+    // Complete request lines received so far
+    fn receive(&mut self) -> Vec<String> {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(n) => self.incoming.extend_from_slice(&buffer[..n]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            }
+        }
+        let mut lines = Vec::new();
+        while let Some(end) = self.incoming.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+}
+
+// Non-blocking server polled from the simulation loop; it never waits for
+// a client, so a stalled or slow planner cannot freeze the simulation
+pub struct ControlServer {
+    listener: TcpListener,
+    clients: Vec<Client>,
+    tick: u64,
+    // Whether the last `handle` swapped in a fresh simulation
+    reset: bool,
+}
+
+impl ControlServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(ControlServer { listener, clients: Vec::new(), tick: 0, reset: false })
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    // True when a client reset the simulation during the last `handle`, so
+    // the caller can drop whatever it recorded about the previous run
+    pub fn reset_applied(&self) -> bool {
+        self.reset
+    }
+
+    // Accepts new clients and applies every request received since the
+    // last call, in arrival order. Pause and resume set `paused`, which the
+    // caller owns. Returns the number of requests applied.
+    pub fn handle(&mut self, sim: &mut Simulation, paused: &mut bool) -> usize {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                let _ = stream.set_nodelay(true);
+                self.clients.push(Client { stream, incoming: Vec::new(), outgoing: Vec::new(), closed: false });
+            }
+        }
+
+        let mut applied = 0;
+        self.reset = false;
+        for client in self.clients.iter_mut() {
+            for line in client.receive() {
+                let result = serde_json::from_str::<Request>(&line)
+                    .map_err(|err| format!("bad request {:?}: {}", line, err))
+                    .and_then(|request| {
+                        let reset = matches!(request, Request::Reset { .. });
+                        apply(request, sim, paused).map(|()| reset)
+                    });
+                match result {
+                    Ok(reset) => {
+                        applied += 1;
+                        self.reset |= reset;
+                    }
+                    Err(message) => client.send(&Message::Error { message }),
+                }
+            }
+        }
+        if applied > 0 {
+            // Let every client see the effect even while paused
+            self.broadcast_state(sim, *paused, &[]);
+        }
+        self.clients.retain(|client| !client.closed);
+        applied
+    }
+
+    // Streams the state and the events after a tick
+    pub fn tick(&mut self, sim: &Simulation, paused: bool) {
+        self.tick += 1;
+        self.broadcast_state(sim, paused, sim.events.recent());
+    }
+
+    fn broadcast_state(&mut self, sim: &Simulation, paused: bool, events: &[Event]) {
+        if self.clients.is_empty() {
+            return;
+        }
+        let state = Message::state(sim, self.tick, paused, events);
+        for client in self.clients.iter_mut() {
+            client.send(&state);
+        }
+        self.clients.retain(|client| !client.closed);
+    }
+}
+
+fn apply(request: Request, sim: &mut Simulation, paused: &mut bool) -> Result<(), String> {
+    match request {
+        Request::Command { id, velocity } => {
+            if !velocity.is_finite() {
+                return Err(format!("velocity must be a number, got {}", velocity));
+            }
+            let index = sim.index_of(id).ok_or(format!("no car with id {}", id.0))?;
+            if !sim.command(index, velocity) {
+                return Err(format!("car {} is human-driven", id));
+            }
+        }
+        Request::Spawn { route } => {
+            if !ROUTES.contains(&route.as_str()) {
+                return Err(format!("unknown route {:?}", route));
+            }
+            sim.request(&route);
+        }
+        Request::Pause => *paused = true,
+        Request::Resume => *paused = false,
+        Request::Reset { seed } => sim.reset(seed.unwrap_or(0)),
+        Request::Policy { name } => {
+            sim.policy = Policy::from_name(&name).ok_or(format!("unknown policy {:?}", name))?;
+        }
+    }
+    Ok(())
+}
+
+// Blocking client for the protocol: the stand-in external controller used
+// by the tests and `examples/control_client.rs`
+pub struct ControlClient {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl ControlClient {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(ControlClient { writer: stream.try_clone()?, reader: BufReader::new(stream) })
+    }
+
+    pub fn set_timeout(&self, timeout: Option<std::time::Duration>) -> io::Result<()> {
+        self.writer.set_read_timeout(timeout)
+    }
+
+    pub fn send(&mut self, request: &Request) -> io::Result<()> {
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)
+    }
+
+    // The next message from the server
+    pub fn receive(&mut self) -> io::Result<Message> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "server closed the connection"));
+        }
+        serde_json::from_str(&line).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventKind;
+    use crate::sim::MAX_TICK;
+    use std::time::{Duration, Instant};
+
+    // Polls the server until it has applied `count` requests
+    fn handle_until(server: &mut ControlServer, sim: &mut Simulation, paused: &mut bool, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut applied = 0;
+        while applied < count {
+            assert!(Instant::now() < deadline, "requests did not arrive");
+            applied += server.handle(sim, paused);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn next_state(client: &mut ControlClient) -> (u64, bool, Vec<CarState>, Vec<Event>) {
+        match client.receive().unwrap() {
+            Message::State { tick, paused, cars, events, .. } => (tick, paused, cars, events),
+            other => panic!("expected a state, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn external_client_spawns_commands_and_pauses() {
+        let mut server = ControlServer::bind("127.0.0.1:0").unwrap();
+        let mut client = ControlClient::connect(server.local_addr().unwrap()).unwrap();
+        client.set_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut sim = Simulation::new();
+        let mut paused = false;
+
+        client.send(&Request::Policy { name: "external".to_string() }).unwrap();
+        client.send(&Request::Spawn { route: "up_stright".to_string() }).unwrap();
+        handle_until(&mut server, &mut sim, &mut paused, 2);
+        let (_, _, cars, _) = next_state(&mut client);
+        assert_eq!(cars.len(), 1);
+        assert_eq!(cars[0].route, "up_stright");
+
+        // The external controller stops the car and the command lasts
+        client.send(&Request::Command { id: cars[0].id, velocity: 0.0 }).unwrap();
+        handle_until(&mut server, &mut sim, &mut paused, 1);
+        next_state(&mut client);
+        for _ in 0..120 {
+            sim.tick(MAX_TICK);
+            server.tick(&sim, paused);
+        }
+        let states: Vec<_> = (0..120).map(|_| next_state(&mut client)).collect();
+        // Events come with the tick that reported them
+        let spawned = Event { time: 0.0, vehicle: cars[0].id, kind: EventKind::Spawned };
+        assert!(states[0].3.contains(&spawned));
+        assert!(states.iter().any(|state| state.3.iter().any(|event| event.kind == EventKind::Stopped)));
+        let (tick, _, cars, _) = states.last().unwrap();
+        assert_eq!(*tick, 120);
+        assert_eq!(cars[0].velocity, 0.0);
+
+        client.send(&Request::Pause).unwrap();
+        handle_until(&mut server, &mut sim, &mut paused, 1);
+        assert!(paused);
+        assert!(next_state(&mut client).1);
+    }
+
+    #[test]
+    fn reset_is_reported_for_one_handle_only() {
+        let mut server = ControlServer::bind("127.0.0.1:0").unwrap();
+        let mut client = ControlClient::connect(server.local_addr().unwrap()).unwrap();
+        let mut sim = Simulation::new();
+        let mut paused = false;
+
+        client.send(&Request::Pause).unwrap();
+        handle_until(&mut server, &mut sim, &mut paused, 1);
+        assert!(!server.reset_applied());
+        client.send(&Request::Reset { seed: Some(7) }).unwrap();
+        handle_until(&mut server, &mut sim, &mut paused, 1);
+        assert!(server.reset_applied());
+        server.handle(&mut sim, &mut paused);
+        assert!(!server.reset_applied());
+    }
+
+    #[test]
+    fn bad_requests_are_answered_with_errors() {
+        let mut server = ControlServer::bind("127.0.0.1:0").unwrap();
+        let mut client = ControlClient::connect(server.local_addr().unwrap()).unwrap();
+        client.set_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut sim = Simulation::new();
+        let mut paused = false;
+        sim.config.human.penetration = 0.0;
+        sim.request("up_stright");
+
+        client.writer.write_all(b"{\"type\":\"fly\"}\n").unwrap();
+        client.send(&Request::Command { id: VehicleId(9), velocity: 1.0 }).unwrap();
+        client.send(&Request::Command { id: VehicleId(0), velocity: 1.0 }).unwrap();
+        client.send(&Request::Spawn { route: "sideways".to_string() }).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while server.clients() == 0 && Instant::now() < deadline {
+            server.handle(&mut sim, &mut paused);
+        }
+        for expected in ["bad request", "no car with id 9", "car #0 is human-driven", "unknown route"] {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            let message = loop {
+                assert!(Instant::now() < deadline, "no answer to the request expected to fail with {:?}", expected);
+                server.handle(&mut sim, &mut paused);
+                client.set_timeout(Some(Duration::from_millis(20))).unwrap();
+                if let Ok(message) = client.receive() {
+                    break message;
+                }
+            };
+            match message {
+                Message::Error { message } => assert!(message.contains(expected), "{}", message),
+                other => panic!("expected an error, got {:?}", other),
+            }
+        }
+        assert_eq!(sim.cars.len(), 1);
+    }
+}
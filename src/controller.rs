@@ -0,0 +1,425 @@
+use crate::cars::{spawn_point, Car, ROUTES};
+use crate::config::{Config, ControllerConfig};
+use crate::cyclists::{bike_crossing, crossing_arm, Cyclist};
+use crate::pedestrians::{crosswalk, distance_ahead, exit_arm, exit_crossing, overlaps, Pedestrian, ARMS};
+use crate::spatial::{Grid, Rect};
+use crate::world::*;
+use std::sync::OnceLock;
+
+// Smart Intersection Velocity Constants. The cruise, adjust and crawl
+// velocities, the safety distance and the right-of-way radii are tunable
+// and live in `ControllerConfig`.
+pub const V_STOP: f32 = 0.0;
+
+// Why the controller chose a car's target velocity. The index is the other
+// car (in the `cars` slice passed to `plan`), the pedestrian (in the
+// `pedestrians` slice) or the cyclist (in `cyclists`) that imposed the limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reason {
+    Clear,
+    Following(usize),
+    Yielding(usize),
+    TieBreak(usize),
+    Pedestrian(usize),
+    Cyclist(usize),
+    External, // Set by a controller outside the simulation
+}
+
+// The velocity band a command falls into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Cruise,
+    Adjust,
+    Crawl,
+    Stop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Command {
+    pub velocity: f32,
+    pub reason: Reason,
+}
+
+impl Command {
+    // Only the most restrictive rule is kept, so the reason always names
+    // the car responsible for the final velocity.
+    fn limit(&mut self, velocity: f32, reason: Reason) {
+        if velocity < self.velocity {
+            self.velocity = velocity;
+            self.reason = reason;
+        }
+    }
+
+    pub fn kind(&self, config: &ControllerConfig) -> Kind {
+        if self.velocity >= config.cruise() {
+            Kind::Cruise
+        } else if self.velocity >= config.adjust() {
+            Kind::Adjust
+        } else if self.velocity > V_STOP {
+            Kind::Crawl
+        } else {
+            Kind::Stop
+        }
+    }
+
+    // The car this one is giving way to at the intersection, if any
+    pub fn yielding_to(&self) -> Option<usize> {
+        match self.reason {
+            Reason::Yielding(j) | Reason::TieBreak(j) => Some(j),
+            _ => None,
+        }
+    }
+
+    // `cars` are the cars the command was planned for, to name the other car
+    pub fn describe(&self, cars: &[Car]) -> String {
+        let car = |j: usize| cars.get(j).map_or("?".to_string(), |car| car.id.to_string());
+        match self.reason {
+            Reason::Clear => "cruising, road clear".to_string(),
+            Reason::Following(j) => format!("following car {}", car(j)),
+            Reason::Yielding(j) => format!("yielding to car {}", car(j)),
+            Reason::TieBreak(j) => format!("tie-break with car {}", car(j)),
+            Reason::Pedestrian(_) => "giving way to a pedestrian".to_string(),
+            Reason::Cyclist(_) => "giving way to a cyclist".to_string(),
+            Reason::External => "commanded externally".to_string(),
+        }
+    }
+}
+
+// Whether cars on routes `a` and `b` (indices into `ROUTES`) can meet in
+// the intersection box. Found once by driving a car along every route and
+// widening its body to the whole lane, so any car that fits a lane is covered.
+pub fn paths_cross(a: usize, b: usize) -> bool {
+    static CROSSING: OnceLock<[[bool; ROUTES.len()]; ROUTES.len()]> = OnceLock::new();
+    let crossing = CROSSING.get_or_init(|| {
+        let paths: Vec<Vec<Rect>> = ROUTES.iter().map(|route| box_path(route)).collect();
+        let mut crossing = [[false; ROUTES.len()]; ROUTES.len()];
+        for (a, row) in crossing.iter_mut().enumerate() {
+            for (b, cell) in row.iter_mut().enumerate() {
+                *cell = a != b && paths[a].iter().any(|&p| paths[b].iter().any(|&q| overlaps(p, q)));
+            }
+        }
+        crossing
+    });
+    crossing[a][b]
+}
+
+// The lane-wide footprints of a car driving `route` through the box
+fn box_path(route: &str) -> Vec<Rect> {
+    const LANE_MARGIN: f32 = 0.5; // Each side of a default car, to the lane width
+    let Some((cord, rotation)) = spawn_point(route) else {
+        return Vec::new();
+    };
+    let zone = intersection_box();
+    let mut car = Car::new(route.to_string(), cord, rotation, 0.0, &Config::default());
+    let mut path = Vec::new();
+    while car.travelled < WORLD_WIDTH + WORLD_HEIGHT {
+        car.update(1.0 / 60.0);
+        let body = car.get_rect();
+        if overlaps(body, zone) {
+            path.push(Rect::new(body.x - LANE_MARGIN, body.y - LANE_MARGIN, body.w + 2.0 * LANE_MARGIN, body.h + 2.0 * LANE_MARGIN));
+        }
+    }
+    path
+}
+
+// Geometry the rules need, computed once per car per tick
+struct Geometry {
+    cord: (f32, f32),
+    rect: Rect,
+    radar: Rect,
+    dist_center: f32,
+    reach: Rect, // Bounding box of rect and radar: nothing outside can interact
+    route: Option<usize>,
+    in_box: bool,
+    entering: bool, // Radar reaching into the box, body not in it yet
+    to_box: f32,    // Metres left to the box while entering
+}
+
+impl Geometry {
+    fn of(car: &Car) -> Self {
+        let (cx, cy) = centre();
+        let rect = car.get_rect();
+        let radar = car.get_radar();
+        let zone = intersection_box();
+        let in_box = overlaps(rect, zone);
+        Geometry {
+            cord: car.cord,
+            rect,
+            radar,
+            dist_center: ((car.cord.0 - cx).powi(2) + (car.cord.1 - cy).powi(2)).sqrt(),
+            reach: rect.combine_with(radar),
+            route: ROUTES.iter().position(|route| *route == car.direction),
+            in_box,
+            entering: !in_box && overlaps(radar, zone),
+            to_box: distance_ahead(rect, car.current_heading(), zone).unwrap_or(0.0),
+        }
+    }
+
+    // Both cars are at the box, on paths that meet in it: the box guard
+    // decides between them, not the right-of-way rule
+    fn guarded_with(&self, other: &Geometry) -> bool {
+        let at_box = |g: &Geometry| g.in_box || g.entering;
+        let crossing = matches!((self.route, other.route), (Some(a), Some(b)) if paths_cross(a, b));
+        crossing && at_box(self) && at_box(other) && !(self.in_box && other.in_box)
+    }
+}
+
+// Grid cells must be at least as large as a car's reach so that most
+// neighbours are found in one or two cells
+const NEIGHBOUR_CELL: f32 = 16.0;
+
+// Command for car `i`, considering the cars in `others` in ascending order
+fn decide(i: usize, geometry: &[Geometry], others: impl Iterator<Item = usize>, config: &ControllerConfig) -> Command {
+    let (v_cruise, v_adjust, v_crawl) = (config.cruise(), config.adjust(), config.crawl());
+    let mut command = Command { velocity: v_cruise, reason: Reason::Clear };
+    let me = &geometry[i];
+
+    for j in others {
+        if i == j { continue; }
+        let other = &geometry[j];
+        let dist_between = ((me.cord.0 - other.cord.0).powi(2) + (me.cord.1 - other.cord.1).powi(2)).sqrt();
+
+        // 1. Same Lane Safety (Rear-ending Avoidance)
+        if me.radar.intersect(other.rect).is_some() {
+            if dist_between < config.safety_distance {
+                command.limit(V_STOP, Reason::Following(j)); // Hard safety stop
+            } else if dist_between < config.safety_distance * 1.5 {
+                command.limit(v_crawl, Reason::Following(j)); // Careful follow distance
+            } else {
+                command.limit(v_adjust, Reason::Following(j)); // Adjusting speed
+            }
+        }
+
+        // 2. Intersection Right-of-Way (Cross-traffic)
+        if me.radar.intersect(other.radar).is_some() && me.rect.intersect(other.rect).is_none() && !me.guarded_with(other) {
+            let dist_diff = me.dist_center - other.dist_center;
+
+            if other.dist_center < config.yield_radius && me.dist_center >= config.yield_radius {
+                // Other car is deep in intersection; we must yield
+                command.limit(V_STOP, Reason::Yielding(j));
+            } else if dist_diff > config.tie_margin {
+                // Other car is closer to the center
+                if me.dist_center < config.stop_radius {
+                    command.limit(V_STOP, Reason::Yielding(j));
+                } else {
+                    command.limit(v_crawl, Reason::Yielding(j));
+                }
+            } else if dist_diff.abs() <= config.tie_margin && i > j {
+                // Tie-breaker yield
+                command.limit(v_adjust, Reason::TieBreak(j));
+            }
+        }
+    }
+
+    command
+}
+
+// 3. Box guard. A car about to enter the box stops short of it while a car
+// whose path crosses its own is in the box, or is about to enter closer to
+// it (the earlier car on a tie). The box never holds two crossing paths, and
+// the cars in it are never held up by the ones waiting outside.
+fn guard_box(geometry: &[Geometry], commands: &mut [Command]) {
+    let at_box: Vec<usize> = (0..geometry.len()).filter(|&j| geometry[j].in_box || geometry[j].entering).collect();
+    for &i in &at_box {
+        let me = &geometry[i];
+        if !me.entering {
+            continue;
+        }
+        let first = at_box.iter().copied().find(|&j| {
+            let other = &geometry[j];
+            j != i && me.guarded_with(other) && (other.in_box || other.to_box < me.to_box || (other.to_box == me.to_box && j < i))
+        });
+        if let Some(j) = first {
+            commands[i].limit(V_STOP, Reason::Yielding(j));
+        }
+    }
+}
+
+// Smart Intersection System: Velocity Control
+//
+// Only cars whose reach shares a grid cell are compared, so a tick costs
+// roughly O(n) instead of O(n^2). Candidates are visited in index order,
+// which keeps the result identical to `plan_exhaustive`. The box guard only
+// looks at the few cars at the box, whatever their distance.
+pub fn plan(cars: &[Car], config: &ControllerConfig) -> Vec<Command> {
+    let geometry: Vec<Geometry> = cars.iter().map(Geometry::of).collect();
+    let reach: Vec<Rect> = geometry.iter().map(|g| g.reach).collect();
+    let grid = Grid::new(&reach, NEIGHBOUR_CELL);
+
+    let mut candidates = Vec::new();
+    let mut commands: Vec<Command> = (0..cars.len())
+        .map(|i| {
+            grid.query(reach[i], &mut candidates);
+            decide(i, &geometry, candidates.iter().copied(), config)
+        })
+        .collect();
+    guard_box(&geometry, &mut commands);
+    commands
+}
+
+// Right-of-way policy applied by the simulation. `Uncontrolled` is the
+// baseline without any coordination: every car cruises and ignores the
+// others, which shows what the smart controller prevents. Under `External`
+// the simulation plans nothing and keeps the commands it is given, for
+// controllers running outside it (the RL environment, the control server).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    Smart,
+    Uncontrolled,
+    External,
+}
+
+impl Policy {
+    // The policies that drive themselves, as compared by the batch runner
+    pub const ALL: [Policy; 2] = [Policy::Smart, Policy::Uncontrolled];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::Smart => "smart",
+            Policy::Uncontrolled => "uncontrolled",
+            Policy::External => "external",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Policy> {
+        [Policy::Smart, Policy::Uncontrolled, Policy::External].into_iter().find(|policy| policy.name() == name)
+    }
+
+    // Nothing for `External`: the current commands stand
+    pub fn plan(&self, cars: &[Car], pedestrians: &[Pedestrian], cyclists: &[Cyclist], config: &ControllerConfig) -> Option<Vec<Command>> {
+        match self {
+            Policy::Smart => {
+                let mut commands = plan(cars, config);
+                yield_to_pedestrians(cars, pedestrians, &mut commands);
+                yield_to_cyclists(cars, cyclists, &mut commands);
+                Some(commands)
+            }
+            Policy::Uncontrolled => Some(vec![Command { velocity: config.cruise(), reason: Reason::Clear }; cars.len()]),
+            Policy::External => None,
+        }
+    }
+}
+
+// Pedestrian rules on top of a plan. Turning cars give way to anyone
+// walking on the crossing they turn into, waiting outside the box, and on
+// any crossing their radar reaches (a car already on it drives on, clearing
+// it). Every car stops for a pedestrian right in front of it; straight
+// traffic otherwise has priority and pedestrians wait for a gap.
+pub fn yield_to_pedestrians(cars: &[Car], pedestrians: &[Pedestrian], commands: &mut [Command]) {
+    let walking: Vec<(usize, &Pedestrian)> = pedestrians.iter().enumerate().filter(|(_, p)| p.walking()).collect();
+    if walking.is_empty() {
+        return;
+    }
+    let zone = intersection_box();
+    for (car, command) in cars.iter().zip(commands.iter_mut()) {
+        let radar = car.get_radar();
+        if let Some(&(k, _)) = walking.iter().find(|(_, p)| overlaps(radar, p.get_rect())) {
+            command.limit(V_STOP, Reason::Pedestrian(k)); // Hard safety stop
+            continue;
+        }
+        let Some(exit) = exit_crossing(&car.direction) else {
+            continue;
+        };
+        let body = car.get_rect();
+        let entering = overlaps(radar, zone) && !overlaps(body, zone);
+        let blocking = walking.iter().find(|(_, p)| {
+            let crossing = crosswalk(p.arm);
+            (p.arm == exit && entering) || (overlaps(radar, crossing) && !overlaps(body, crossing))
+        });
+        if let Some(&(k, _)) = blocking {
+            command.limit(V_STOP, Reason::Pedestrian(k));
+        }
+    }
+}
+
+// Cyclist rules on top of a plan, like the pedestrian ones with the bike
+// crossings for crosswalks: turning cars wait outside the box while a
+// cyclist rides over the crossing they turn into, and every car stops for a
+// cyclist right in front of it. A bike crossing spans the whole road and
+// takes seconds to ride over, so straight traffic too stops short of one in
+// use. Right-turning cars also wait for cyclists riding up beside them from
+// the same approach (the right hook).
+pub fn yield_to_cyclists(cars: &[Car], cyclists: &[Cyclist], commands: &mut [Command]) {
+    if cyclists.is_empty() {
+        return;
+    }
+    let zone = intersection_box();
+    for (car, command) in cars.iter().zip(commands.iter_mut()) {
+        let radar = car.get_radar();
+        if let Some(k) = cyclists.iter().position(|cyclist| overlaps(radar, cyclist.get_rect())) {
+            command.limit(V_STOP, Reason::Cyclist(k)); // Hard safety stop
+            continue;
+        }
+        let turning = exit_crossing(&car.direction).is_some();
+        let exit = exit_arm(&car.direction);
+        let body = car.get_rect();
+        let entering = turning && overlaps(radar, zone) && !overlaps(body, zone);
+        let blocking = cyclists.iter().position(|cyclist| {
+            let crossing = bike_crossing(cyclist.approach, cyclist.lane_width);
+            let hook = car.direction == format!("{}_right", ARMS[cyclist.approach]) && cyclist.hookable();
+            (entering && (hook || (cyclist.crossing && exit == Some(crossing_arm(cyclist.approach)))))
+                || (cyclist.crossing && overlaps(radar, crossing) && !overlaps(body, crossing))
+        });
+        if let Some(k) = blocking {
+            command.limit(V_STOP, Reason::Cyclist(k));
+        }
+    }
+}
+
+// Reference implementation comparing every pair; used by the tests and
+// benchmarks to check and measure `plan`
+pub fn plan_exhaustive(cars: &[Car], config: &ControllerConfig) -> Vec<Command> {
+    let geometry: Vec<Geometry> = cars.iter().map(Geometry::of).collect();
+    let mut commands: Vec<Command> = (0..cars.len()).map(|i| decide(i, &geometry, 0..cars.len(), config)).collect();
+    guard_box(&geometry, &mut commands);
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cars::{approaching, stress_layout};
+    use crate::sim::{Simulation, MAX_TICK};
+
+    #[test]
+    fn grid_plan_matches_exhaustive_plan() {
+        for count in [0, 1, 12, 240, 1000] {
+            let cars = stress_layout(count, count as u64);
+            let config = ControllerConfig::default();
+            assert_eq!(plan(&cars, &config), plan_exhaustive(&cars, &config), "{} cars", count);
+        }
+    }
+
+    #[test]
+    fn only_one_of_two_crossing_paths_enters_the_box() {
+        let route = |name: &str| ROUTES.iter().position(|route| *route == name).unwrap();
+        assert!(paths_cross(route("up_stright"), route("right_stright")));
+        assert!(paths_cross(route("up_left"), route("down_stright")));
+        assert!(!paths_cross(route("up_stright"), route("down_stright")));
+        assert!(!paths_cross(route("up_right"), route("down_right")));
+
+        // Both just short of the box, the second a little further from it
+        let at_box = |name: &str, back: f32| {
+            let car = approaching(name, 0.0);
+            let run = distance_ahead(car.get_rect(), car.current_heading(), intersection_box()).unwrap();
+            approaching(name, run - back)
+        };
+        let cars = [at_box("up_stright", 3.0), at_box("right_stright", 2.0)];
+        let commands = plan(&cars, &ControllerConfig::default());
+        assert_eq!(commands[0], Command { velocity: V_STOP, reason: Reason::Yielding(1) });
+        assert_eq!(commands[1].reason, Reason::Clear);
+    }
+
+    #[test]
+    fn grid_plan_matches_exhaustive_plan_in_traffic() {
+        let mut sim = Simulation::with_seed(7);
+        for tick in 0..60 * 60 {
+            if tick % 20 == 0 {
+                sim.request_random(&crate::cars::ROUTES);
+            }
+            sim.tick(MAX_TICK);
+            let config = &sim.config.controller;
+            assert_eq!(plan(&sim.cars, config), plan_exhaustive(&sim.cars, config), "tick {}", tick);
+        }
+    }
+}
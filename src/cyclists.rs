@@ -0,0 +1,228 @@
+use crate::cars::{Car, VehicleId};
+use crate::config::BikeConfig;
+use crate::pedestrians::{arm_towards, distance_ahead, exit_arm, heading, overlaps, Pedestrian};
+use crate::spatial::Rect;
+use crate::world::*;
+
+// Cyclists ride straight through in a bike lane on the right of each
+// approach, beside the outer (right-turn) lane, just off the road edge. The
+// bike lane meets traffic where it crosses the road on its right, beside the
+// box: the bike crossing. Cars turning right from the same approach cross it
+// too, right across the path of a cyclist riding up alongside (the right
+// hook), so they wait for cyclists coming up within `HOOK_DISTANCE`.
+pub const CYCLIST_LENGTH: f32 = 1.8;
+pub const CYCLIST_WIDTH: f32 = 0.6;
+pub const LOOKAHEAD: f32 = 4.0; // Metres ahead a cyclist watches
+pub const HOOK_DISTANCE: f32 = 15.0;
+
+fn right_of((hx, hy): (i32, i32)) -> (i32, i32) {
+    (-hy, hx)
+}
+
+// Distance from the entry at the world edge to the centre, along the lane
+fn half_run(approach: usize) -> f32 {
+    if matches!(approach, 0 | 2) { WORLD_HEIGHT / 2.0 } else { WORLD_WIDTH / 2.0 }
+}
+
+// The whole bike lane of `approach`, edge to edge of the world
+pub fn bike_lane(approach: usize, lane_width: f32) -> Rect {
+    let (cx, cy) = centre();
+    let b = BOX_HALF_WIDTH;
+    match approach {
+        0 => Rect::new(cx + b, 0.0, lane_width, WORLD_HEIGHT),
+        1 => Rect::new(0.0, cy + b, WORLD_WIDTH, lane_width),
+        2 => Rect::new(cx - b - lane_width, 0.0, lane_width, WORLD_HEIGHT),
+        _ => Rect::new(0.0, cy - b - lane_width, WORLD_WIDTH, lane_width),
+    }
+}
+
+// Where the bike lane of `approach` crosses the road on its right
+pub fn bike_crossing(approach: usize, lane_width: f32) -> Rect {
+    let (cx, cy) = centre();
+    let b = BOX_HALF_WIDTH;
+    match approach {
+        0 => Rect::new(cx + b, cy - b, lane_width, 2.0 * b),
+        1 => Rect::new(cx - b, cy + b, 2.0 * b, lane_width),
+        2 => Rect::new(cx - b - lane_width, cy - b, lane_width, 2.0 * b),
+        _ => Rect::new(cx - b, cy - b - lane_width, 2.0 * b, lane_width),
+    }
+}
+
+// Arm whose road the bike crossing of `approach` lies on
+pub fn crossing_arm(approach: usize) -> usize {
+    arm_towards(right_of(heading(approach)))
+}
+
+#[derive(Clone, Debug)]
+pub struct Cyclist {
+    pub approach: usize,
+    pub travelled: f32, // Metres from the entry
+    pub velocity: f32,  // m/s; cyclists ride at their speed or stand
+    pub speed: f32,
+    pub lane_width: f32, // Of the lane they entered, kept if the config changes
+    pub crossing: bool,  // Took a gap and is riding over the bike crossing
+    pub spawn_time: f64,
+    pub near: Vec<VehicleId>, // Cars whose radar reaches this cyclist
+}
+
+impl Cyclist {
+    pub fn new(approach: usize, config: &BikeConfig, spawn_time: f64) -> Self {
+        Cyclist {
+            approach,
+            travelled: 0.0,
+            velocity: config.speed(),
+            speed: config.speed(),
+            lane_width: config.lane_width,
+            crossing: false,
+            spawn_time,
+            near: Vec::new(),
+        }
+    }
+
+    pub fn heading(&self) -> (f32, f32) {
+        let (hx, hy) = heading(self.approach);
+        (hx as f32, hy as f32)
+    }
+
+    // Entry to exit, off the world at both ends
+    pub fn run_length(&self) -> f32 {
+        2.0 * half_run(self.approach) + CYCLIST_LENGTH
+    }
+
+    pub fn done(&self) -> bool {
+        self.travelled >= self.run_length()
+    }
+
+    fn rect_at(&self, travelled: f32) -> Rect {
+        let (cx, cy) = centre();
+        let (hx, hy) = self.heading();
+        let (rx, ry) = right_of(heading(self.approach));
+        let side = BOX_HALF_WIDTH + self.lane_width / 2.0;
+        let along = travelled - half_run(self.approach) - CYCLIST_LENGTH / 2.0;
+        let (x, y) = (cx + rx as f32 * side + hx * along, cy + ry as f32 * side + hy * along);
+        let (w, h) = if hx == 0.0 { (CYCLIST_WIDTH, CYCLIST_LENGTH) } else { (CYCLIST_LENGTH, CYCLIST_WIDTH) };
+        Rect::new(x - w / 2.0, y - h / 2.0, w, h)
+    }
+
+    pub fn get_rect(&self) -> Rect {
+        self.rect_at(self.travelled)
+    }
+
+    // The `LOOKAHEAD` metres in front
+    pub fn get_radar(&self) -> Rect {
+        let body = self.get_rect();
+        let (hx, hy) = self.heading();
+        match (hx > 0.0, hx < 0.0, hy > 0.0) {
+            (true, _, _) => Rect::new(body.right(), body.y, LOOKAHEAD, body.h),
+            (_, true, _) => Rect::new(body.x - LOOKAHEAD, body.y, LOOKAHEAD, body.h),
+            (_, _, true) => Rect::new(body.x, body.bottom(), body.w, LOOKAHEAD),
+            _ => Rect::new(body.x, body.y - LOOKAHEAD, body.w, LOOKAHEAD),
+        }
+    }
+
+    // Rides on unless something is right ahead, or the bike crossing is
+    // coming up without a gap in the traffic. `others` are the bodies of
+    // every cyclist, this one included.
+    pub fn update(&mut self, dt: f32, cars: &[Car], pedestrians: &[Pedestrian], others: &[Rect], config: &BikeConfig, cruise: f32) {
+        let body = self.get_rect();
+        let radar = self.get_radar();
+        let zone = bike_crossing(self.approach, self.lane_width);
+        if self.crossing && distance_ahead(body, self.heading(), zone).is_none() {
+            self.crossing = false;
+        }
+        let mut blocked = cars.iter().any(|car| overlaps(radar, car.get_rect()))
+            || pedestrians.iter().any(|pedestrian| overlaps(radar, pedestrian.get_rect()))
+            || others.iter().any(|other| overlaps(radar, *other));
+        if !blocked && !self.crossing && overlaps(radar, zone) && distance_ahead(body, self.heading(), zone).is_some() {
+            if self.gap_accepted(cars, config, cruise) {
+                self.crossing = true;
+            } else {
+                blocked = true;
+            }
+        }
+        self.velocity = if blocked { 0.0 } else { self.speed };
+        self.travelled += self.velocity * dt;
+    }
+
+    // True when no car is on the bike crossing or about to leave the box
+    // over it, and every car heading for it, driving at `cruise`, gets there
+    // `gap_margin` seconds after this cyclist has ridden past its lane
+    pub fn gap_accepted(&self, cars: &[Car], config: &BikeConfig, cruise: f32) -> bool {
+        let zone = bike_crossing(self.approach, self.lane_width);
+        let arm = crossing_arm(self.approach);
+        let body = self.get_rect();
+        let (hx, hy) = self.heading();
+        let box_zone = intersection_box();
+        cars.iter().all(|car| {
+            let car_body = car.get_rect();
+            if overlaps(car_body, zone) || (exit_arm(&car.direction) == Some(arm) && overlaps(car_body, box_zone)) {
+                return false;
+            }
+            let Some(distance) = distance_ahead(car_body, car.current_heading(), zone) else {
+                return true;
+            };
+            // How far this cyclist's front has to ride to be past the car's lane
+            let to_clear = match (hx > 0.0, hx < 0.0, hy > 0.0) {
+                (true, _, _) => car_body.right() - body.right(),
+                (_, true, _) => body.x - car_body.x,
+                (_, _, true) => car_body.bottom() - body.bottom(),
+                _ => body.y - car_body.y,
+            } + CYCLIST_LENGTH;
+            to_clear <= 0.0 || distance / cruise > to_clear / self.speed + config.gap_margin
+        })
+    }
+
+    // Whether a right-turning car from the same approach would hook this
+    // cyclist: riding up within `HOOK_DISTANCE` of the bike crossing, or on
+    // it. One standing at the crossing waits for a gap, and the car goes
+    // first: waiting for it could lock both up.
+    pub fn hookable(&self) -> bool {
+        let zone = bike_crossing(self.approach, self.lane_width);
+        let near = distance_ahead(self.get_rect(), self.heading(), zone).is_some_and(|distance| distance <= HOOK_DISTANCE);
+        self.crossing || (near && self.velocity > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pedestrians::ARMS;
+
+    #[test]
+    fn bike_lanes_run_beside_the_outer_lane_and_cross_the_road_on_the_right() {
+        let config = BikeConfig::default();
+        for (approach, name) in ARMS.iter().enumerate() {
+            let lane = bike_lane(approach, config.lane_width);
+            let mut cyclist = Cyclist::new(approach, &config, 0.0);
+            cyclist.travelled = 20.0;
+            assert!(lane.contains(cyclist.get_rect().center()), "{}", name);
+            assert!(!overlaps(lane, intersection_box()), "{}", name);
+            // Right turns from this approach leave through the bike crossing's road
+            assert_eq!(exit_arm(&format!("{}_right", name)), Some(crossing_arm(approach)), "{}", name);
+            let crossing = bike_crossing(approach, config.lane_width);
+            assert!(overlaps(crossing, lane), "{}", name);
+        }
+    }
+
+    #[test]
+    fn cyclists_wait_for_cross_traffic_at_the_bike_crossing() {
+        let config = BikeConfig::default();
+        let cruise = kmh(144.0);
+        let mut cyclist = Cyclist::new(0, &config, 0.0);
+        // Right before the crossing
+        let zone = bike_crossing(0, config.lane_width);
+        cyclist.travelled = half_run(0) + CYCLIST_LENGTH / 2.0 - (zone.bottom() - centre().1) - LOOKAHEAD / 2.0;
+        assert!(overlaps(cyclist.get_radar(), zone));
+        assert!(cyclist.hookable());
+
+        let inbound = |route: &str| crate::cars::approaching(route, 100.0);
+        assert!(cyclist.gap_accepted(&[], &config, cruise));
+        assert!(!cyclist.gap_accepted(&[inbound("left_stright")], &config, cruise));
+        assert!(cyclist.gap_accepted(&[inbound("down_stright")], &config, cruise));
+
+        cyclist.update(0.1, &[inbound("left_stright")], &[], &[], &config, cruise);
+        assert_eq!(cyclist.velocity, 0.0);
+        cyclist.update(0.1, &[], &[], &[], &config, cruise);
+        assert!(cyclist.crossing && cyclist.velocity > 0.0);
+    }
+}
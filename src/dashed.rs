@@ -1,13 +1,13 @@
+use crate::world::*;
 use macroquad::prelude::*;
 pub fn draw_dashed_middle_lines(gap: f32) {
-    let w = screen_width();
-    let h = screen_height();
-    let cx = w / 2.0;
-    let cy = h / 2.0;
-    let gapp = 120.0;
-    let dash = 18.0;
-    let space = 12.0;
-    let thickness = 2.0;
+    let w = WORLD_WIDTH;
+    let h = WORLD_HEIGHT;
+    let (cx, cy) = centre();
+    let gapp = 12.0;
+    let dash = 1.8;
+    let space = 1.2;
+    let thickness = 0.2;
 
     draw_dashed_line(
         vec2(cx, 0.0),
@@ -44,16 +44,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
     );
     // dashed for right lane 1
     draw_dashed_line(
-        vec2(0.0, cy - 80.0),
-        vec2(cx - gapp, cy - 80.0),
+        vec2(0.0, cy - 8.0),
+        vec2(cx - gapp, cy - 8.0),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(0.0, cy - 40.0),
-        vec2(cx - gapp, cy - 40.0),
+        vec2(0.0, cy - 4.0),
+        vec2(cx - gapp, cy - 4.0),
         dash,
         space,
         thickness,
@@ -61,16 +61,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
     );
     // dashed for right lane 2
     draw_dashed_line(
-        vec2(0.0, cy + 40.0),
-        vec2(cx - gapp, cy + 40.0),
+        vec2(0.0, cy + 4.0),
+        vec2(cx - gapp, cy + 4.0),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(0.0, cy + 80.0),
-        vec2(cx - gapp, cy + 80.0),
+        vec2(0.0, cy + 8.0),
+        vec2(cx - gapp, cy + 8.0),
         dash,
         space,
         thickness,
@@ -78,16 +78,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
     );
     // dashed for top lane 1
     draw_dashed_line(
-        vec2(cx - 80.0, h),
-        vec2(cx - 80.0, cy + gapp),
+        vec2(cx - 8.0, h),
+        vec2(cx - 8.0, cy + gapp),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(cx - 40.0, h),
-        vec2(cx - 40.0, cy + gapp),
+        vec2(cx - 4.0, h),
+        vec2(cx - 4.0, cy + gapp),
         dash,
         space,
         thickness,
@@ -95,16 +95,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
     );
     // dashed for top lane 2
     draw_dashed_line(
-        vec2(cx + 40.0, h),
-        vec2(cx + 40.0, cy + gapp),
+        vec2(cx + 4.0, h),
+        vec2(cx + 4.0, cy + gapp),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(cx + 80.0, h),
-        vec2(cx + 80.0, cy + gapp),
+        vec2(cx + 8.0, h),
+        vec2(cx + 8.0, cy + gapp),
         dash,
         space,
         thickness,
@@ -112,16 +112,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
     );
     // dashed for left lane 1
     draw_dashed_line(
-        vec2(w, cy + 80.0),
-        vec2(cx + gapp, cy + 80.0),
+        vec2(w, cy + 8.0),
+        vec2(cx + gapp, cy + 8.0),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(w, cy + 40.0),
-        vec2(cx + gapp, cy + 40.0),
+        vec2(w, cy + 4.0),
+        vec2(cx + gapp, cy + 4.0),
         dash,
         space,
         thickness,
@@ -129,16 +129,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
     );
     // dashed for left lane 2
     draw_dashed_line(
-        vec2(w, cy -40.0),
-        vec2(cx + gapp, cy - 40.0),
+        vec2(w, cy -4.0),
+        vec2(cx + gapp, cy - 4.0),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(w, cy -80.0),
-        vec2(cx + gapp, cy - 80.0),
+        vec2(w, cy -8.0),
+        vec2(cx + gapp, cy - 8.0),
         dash,
         space,
         thickness,
@@ -146,16 +146,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
     );
     // dashed for up lane 1
     draw_dashed_line(
-        vec2(cx + 80.0, 0.0),
-        vec2(cx + 80.0, cy - gapp),
+        vec2(cx + 8.0, 0.0),
+        vec2(cx + 8.0, cy - gapp),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(cx + 40.0, 0.0),
-        vec2(cx + 40.0, cy - gapp),
+        vec2(cx + 4.0, 0.0),
+        vec2(cx + 4.0, cy - gapp),
         dash,
         space,
         thickness,
@@ -164,16 +164,16 @@ pub fn draw_dashed_middle_lines(gap: f32) {
 
     // dashed for up lane 2
     draw_dashed_line(
-        vec2(cx - 40.0, 0.0),
-        vec2(cx - 40.0, cy - gapp),
+        vec2(cx - 4.0, 0.0),
+        vec2(cx - 4.0, cy - gapp),
         dash,
         space,
         thickness,
         WHITE,
     );
     draw_dashed_line(
-        vec2(cx - 80.0, 0.0),
-        vec2(cx - 80.0, cy - gapp),
+        vec2(cx - 8.0, 0.0),
+        vec2(cx - 8.0, cy - gapp),
         dash,
         space,
         thickness,
@@ -0,0 +1,57 @@
+use crate::cars::Car;
+use crate::config::ControllerConfig;
+use crate::controller::*;
+use crate::world::*;
+use macroquad::prelude::*;
+
+fn kind_color(kind: Kind) -> Color {
+    match kind {
+        Kind::Cruise => GREEN,
+        Kind::Adjust => YELLOW,
+        Kind::Crawl => ORANGE,
+        Kind::Stop => RED,
+    }
+}
+
+fn draw_arrow(from: Vec2, to: Vec2, thickness: f32, color: Color) {
+    let dir = to - from;
+    let len = dir.length();
+    if len <= 0.0 {
+        return;
+    }
+    let dir = dir / len;
+    let side = vec2(-dir.y, dir.x);
+    let head = 1.5;
+    draw_line(from.x, from.y, to.x, to.y, thickness, color);
+    draw_triangle(to, to - dir * head + side * head * 0.5, to - dir * head - side * head * 0.5, color);
+}
+
+// Controller internals drawn over the world (in world coordinates): the
+// yield and stop circles, each car's rect and radar tinted by its current
+// command, an arrow from every yielding car to the car it gives way to, and a
+// magenta outline around cars that overlap.
+pub fn draw_debug(cars: &[Car], commands: &[Command], collisions: &[(usize, usize)], config: &ControllerConfig) {
+    let (cx, cy) = centre();
+    draw_circle_lines(cx, cy, config.yield_radius, 0.2, SKYBLUE);
+    draw_circle_lines(cx, cy, config.stop_radius, 0.2, PINK);
+
+    for (car, command) in cars.iter().zip(commands) {
+        let color = kind_color(command.kind(config));
+        let rect = car.get_rect();
+        let radar = car.get_radar();
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(color.r, color.g, color.b, 0.45));
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 0.2, color);
+        draw_rectangle_lines(radar.x, radar.y, radar.w, radar.h, 0.15, Color::new(color.r, color.g, color.b, 0.7));
+
+        if let Some(other) = command.yielding_to().and_then(|j| cars.get(j)) {
+            draw_arrow(Vec2::from(rect.center()), Vec2::from(other.get_rect().center()), 0.25, WHITE);
+        }
+    }
+
+    for &(i, j) in collisions {
+        for car in [&cars[i], &cars[j]] {
+            let rect = car.get_rect();
+            draw_rectangle_lines(rect.x - 0.3, rect.y - 0.3, rect.w + 0.6, rect.h + 0.6, 0.4, MAGENTA);
+        }
+    }
+}
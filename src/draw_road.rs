@@ -1,88 +1,128 @@
+use crate::cyclists::*;
 use crate::dashed::*;
+use crate::pedestrians::*;
+use crate::world::*;
 use macroquad::prelude::*;
-pub fn draw_road() {
-    let screen_width = screen_width();
-    let screen_height = screen_height();
-    let tickness = 3.0;
-    let gap = 120.0;
+// Drawn in world coordinates (metres), under the world camera, with bike
+// lanes `lane_width` wide beside the road
+pub fn draw_road(lane_width: f32) {
+    let world_width = WORLD_WIDTH;
+    let world_height = WORLD_HEIGHT;
+    let tickness = 0.3;
+    let gap = BOX_HALF_WIDTH;
     let color1 = GOLD;
     draw_line(
-        (screen_width / 2.0) - gap,
-        (screen_height / 2.0) - gap,
-        (screen_width / 2.0) - gap,
+        (world_width / 2.0) - gap,
+        (world_height / 2.0) - gap,
+        (world_width / 2.0) - gap,
         0.0,
         tickness,
         color1,
     );
     draw_line(
-        (screen_width / 2.0) + gap,
-        (screen_height / 2.0) - gap,
-        (screen_width / 2.0) + gap,
+        (world_width / 2.0) + gap,
+        (world_height / 2.0) - gap,
+        (world_width / 2.0) + gap,
         0.0,
         tickness,
         color1,
     );
     draw_line(
-        (screen_width / 2.0) + gap,
-        (screen_height / 2.0) - gap,
-        screen_width,
-        (screen_height / 2.0) - gap,
+        (world_width / 2.0) + gap,
+        (world_height / 2.0) - gap,
+        world_width,
+        (world_height / 2.0) - gap,
         tickness,
         color1,
     );
     draw_line(
-        (screen_width / 2.0) + gap,
-        (screen_height / 2.0) + gap,
-        screen_width,
-        (screen_height / 2.0) + gap,
+        (world_width / 2.0) + gap,
+        (world_height / 2.0) + gap,
+        world_width,
+        (world_height / 2.0) + gap,
         tickness,
         color1,
     );
     draw_line(
-        (screen_width / 2.0) + gap,
-        (screen_height / 2.0) + gap,
-        (screen_width / 2.0) + gap,
-        screen_height,
+        (world_width / 2.0) + gap,
+        (world_height / 2.0) + gap,
+        (world_width / 2.0) + gap,
+        world_height,
         tickness,
         color1,
     );
     draw_line(
-        (screen_width / 2.0) - gap,
-        (screen_height / 2.0) + gap,
-        (screen_width / 2.0) - gap,
-        screen_height,
+        (world_width / 2.0) - gap,
+        (world_height / 2.0) + gap,
+        (world_width / 2.0) - gap,
+        world_height,
         tickness,
         color1,
     );
     draw_line(
-        (screen_width / 2.0) - gap,
-        (screen_height / 2.0) + gap,
+        (world_width / 2.0) - gap,
+        (world_height / 2.0) + gap,
         0.0,
-        (screen_height / 2.0) + gap,
+        (world_height / 2.0) + gap,
         tickness,
         color1,
     );
     draw_line(
-        (screen_width / 2.0) - gap,
-        (screen_height / 2.0) - gap,
+        (world_width / 2.0) - gap,
+        (world_height / 2.0) - gap,
         0.0,
-        (screen_height / 2.0) - gap,
+        (world_height / 2.0) - gap,
         tickness,
         color1,
     );
     draw_rectangle(
-        screen_width / 2.0 - gap,
+        world_width / 2.0 - gap,
         0.0,
         gap * 2.0,
-        screen_height,
+        world_height,
         BLACK,
     );
     draw_rectangle(
         0.0,
-        screen_height / 2.0 - gap,
-        screen_width,
+        world_height / 2.0 - gap,
+        world_width,
         gap * 2.0,
         BLACK,
     );
-    draw_dashed_middle_lines(20.0);
+    draw_dashed_middle_lines(2.0);
+    draw_bike_lanes(lane_width);
+    draw_crosswalks();
+}
+
+// Green lanes outside the road edge, paler where they cross a road
+fn draw_bike_lanes(lane_width: f32) {
+    let lane = Color::new(0.1, 0.45, 0.2, 1.0);
+    let crossing = Color::new(0.2, 0.6, 0.3, 0.6);
+    for approach in 0..ARMS.len() {
+        let zone = bike_lane(approach, lane_width);
+        draw_rectangle(zone.x, zone.y, zone.w, zone.h, lane);
+    }
+    for approach in 0..ARMS.len() {
+        let zone = bike_crossing(approach, lane_width);
+        draw_rectangle(zone.x, zone.y, zone.w, zone.h, crossing);
+    }
+}
+
+// Zebra stripes across each arm, 0.5 m wide every metre
+fn draw_crosswalks() {
+    let stripe = 0.5;
+    for arm in 0..ARMS.len() {
+        let zone = crosswalk(arm);
+        let across_x = zone.w > zone.h;
+        let length = zone.w.max(zone.h);
+        let mut at = stripe / 2.0;
+        while at + stripe <= length {
+            if across_x {
+                draw_rectangle(zone.x + at, zone.y, stripe, zone.h, WHITE);
+            } else {
+                draw_rectangle(zone.x, zone.y + at, zone.w, stripe, WHITE);
+            }
+            at += 2.0 * stripe;
+        }
+    }
 }
@@ -0,0 +1,210 @@
+use crate::cars::{Car, VehicleId};
+use crate::config::HumanConfig;
+use crate::controller::{self, Command, Reason, V_STOP};
+use crate::cyclists::Cyclist;
+use crate::pedestrians::{distance_ahead, overlaps, Pedestrian};
+use crate::rng::Rng;
+use crate::world::*;
+use std::collections::VecDeque;
+
+// Human drivers, for cars that are not connected to the intersection. They
+// ignore the controller's commands: they look ahead as far as they need to
+// stop in time, enter the box only when cross traffic leaves them their
+// critical gap, and act on what they saw one reaction time ago. They give
+// way to pedestrians and cyclists by the same rules as the AVs.
+pub const HEADWAY: f32 = 4.0; // Metres kept to whatever they stop for
+pub const WAITING_DISTANCE: f32 = 8.0; // Closer than this to the box and stopped counts as waiting at the line
+const MOVING: f32 = 0.5; // m/s
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Cautious,
+    Aggressive,
+}
+
+impl Profile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Cautious => "cautious",
+            Profile::Aggressive => "aggressive",
+        }
+    }
+
+    // Factors on the average reaction time, critical gap and speed
+    fn factors(&self) -> (f32, f32, f32) {
+        match self {
+            Profile::Cautious => (1.2, 1.5, 0.9),
+            Profile::Aggressive => (0.8, 0.6, 1.1),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Driver {
+    pub profile: Profile,
+    pub reaction_time: f32, // Seconds
+    pub critical_gap: f32,  // Seconds
+    pub speed: f32,         // m/s they like to drive at
+    acting: Command, // What they are doing now
+    // Decisions not yet acted on, with the time they will be and the car
+    // they were about, which may sit elsewhere in `cars` by then
+    seen: VecDeque<(f64, Command, Option<VehicleId>)>,
+}
+
+impl Driver {
+    // `spread` scales the reaction time and critical gap of this driver
+    pub fn new(profile: Profile, config: &HumanConfig, spread: f32) -> Self {
+        let (reaction, gap, speed) = profile.factors();
+        let speed = config.speed() * speed;
+        Driver {
+            profile,
+            reaction_time: config.reaction_time * reaction * spread,
+            critical_gap: config.critical_gap * gap * spread,
+            speed,
+            acting: Command { velocity: speed, reason: Reason::Clear },
+            seen: VecDeque::new(),
+        }
+    }
+
+    // A driver of a random profile, `cautious_share` of them cautious, and
+    // up to 20% quicker or slower than the profile
+    pub fn random(config: &HumanConfig, rng: &mut Rng) -> Self {
+        let profile = if rng.next_f64() < config.cautious_share as f64 { Profile::Cautious } else { Profile::Aggressive };
+        let spread = 0.8 + 0.4 * rng.next_f64() as f32;
+        Driver::new(profile, config, spread)
+    }
+
+    // How far ahead they watch: what they drive before reacting, then brake
+    pub fn sight(&self, velocity: f32, deceleration: f32) -> f32 {
+        let velocity = velocity.max(self.speed);
+        velocity * self.reaction_time + velocity * velocity / (2.0 * deceleration) + HEADWAY
+    }
+
+    // Takes in what was seen at `now` and returns what the driver does: the
+    // latest decision that is one reaction time old. `ids` are the ids of
+    // the cars on the road, which command reasons refer to by index.
+    pub fn react(&mut self, now: f64, seen: Command, ids: &[VehicleId]) -> Command {
+        let about = other_car(seen.reason).map(|j| ids[j]);
+        self.seen.push_back((now + self.reaction_time as f64, seen, about));
+        while let Some(&(due, mut command, about)) = self.seen.front() {
+            if due > now {
+                break;
+            }
+            // A car that has left since is no reason any more
+            if let Some(id) = about {
+                command.reason = match ids.binary_search(&id) {
+                    Ok(j) => with_other_car(command.reason, j),
+                    Err(_) => Reason::Clear,
+                };
+            }
+            self.acting = command;
+            self.seen.pop_front();
+        }
+        self.acting
+    }
+}
+
+fn other_car(reason: Reason) -> Option<usize> {
+    match reason {
+        Reason::Following(j) | Reason::Yielding(j) | Reason::TieBreak(j) => Some(j),
+        _ => None,
+    }
+}
+
+fn with_other_car(reason: Reason, j: usize) -> Reason {
+    match reason {
+        Reason::Following(_) => Reason::Following(j),
+        Reason::Yielding(_) => Reason::Yielding(j),
+        Reason::TieBreak(_) => Reason::TieBreak(j),
+        other => other,
+    }
+}
+
+// What the driver of every human-driven car would do right now, judging by
+// what they see; index-aligned with `cars`, nothing useful for connected cars
+pub fn perceive(cars: &[Car], pedestrians: &[Pedestrian], cyclists: &[Cyclist]) -> Vec<Command> {
+    let mut commands: Vec<Command> = (0..cars.len()).map(|i| decide(i, cars, pedestrians, cyclists)).collect();
+    controller::yield_to_pedestrians(cars, pedestrians, &mut commands);
+    controller::yield_to_cyclists(cars, cyclists, &mut commands);
+    commands
+}
+
+fn decide(i: usize, cars: &[Car], pedestrians: &[Pedestrian], cyclists: &[Cyclist]) -> Command {
+    let car = &cars[i];
+    let Some(driver) = &car.driver else {
+        return Command { velocity: car.target_velocity, reason: Reason::Clear };
+    };
+    let stop = |reason| Command { velocity: V_STOP, reason };
+    let body = car.get_rect();
+    let sight = car.look_ahead(driver.sight(car.velocity, car.deceleration));
+    if let Some(j) = (0..cars.len()).find(|&j| j != i && overlaps(sight, cars[j].get_rect())) {
+        return stop(Reason::Following(j));
+    }
+    if let Some(k) = pedestrians.iter().position(|pedestrian| overlaps(sight, pedestrian.get_rect())) {
+        return stop(Reason::Pedestrian(k));
+    }
+    if let Some(k) = cyclists.iter().position(|cyclist| overlaps(sight, cyclist.get_rect())) {
+        return stop(Reason::Cyclist(k));
+    }
+
+    // Coming up to the box: cross traffic in it or about to reach it within
+    // the critical gap has to pass first. Of two people waiting at the line,
+    // the one who came first goes first.
+    let zone = intersection_box();
+    if overlaps(sight, zone) && !overlaps(body, zone) {
+        let approach = car.direction.split('_').next();
+        let conflict = (0..cars.len()).find(|&j| {
+            let other = &cars[j];
+            if j == i || other.direction.split('_').next() == approach {
+                return false;
+            }
+            let Some(distance) = distance_ahead(other.get_rect(), other.current_heading(), zone) else {
+                return false;
+            };
+            let arriving = other.velocity > MOVING && distance / other.velocity < driver.critical_gap;
+            let waiting = !other.connected() && other.velocity <= MOVING && distance < WAITING_DISTANCE && other.id < car.id;
+            distance == 0.0 || arriving || waiting
+        });
+        if let Some(j) = conflict {
+            return stop(Reason::Yielding(j));
+        }
+    }
+    Command { velocity: driver.speed, reason: Reason::Clear }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cars::approaching as car;
+
+    #[test]
+    fn drivers_act_one_reaction_time_late() {
+        let config = HumanConfig::default();
+        let mut driver = Driver::new(Profile::Cautious, &config, 1.0);
+        assert_eq!(driver.reaction_time, 1.2);
+        let stop = Command { velocity: V_STOP, reason: Reason::Following(1) };
+        let ids = [VehicleId(3), VehicleId(5)];
+        assert_eq!(driver.react(0.0, stop, &ids).velocity, driver.speed);
+        assert_eq!(driver.react(1.0, stop, &ids).velocity, driver.speed);
+        // Car #3 left meanwhile: #5 is the first car now
+        let go = Command { velocity: driver.speed, reason: Reason::Clear };
+        assert_eq!(driver.react(1.25, go, &ids[1..]), Command { velocity: V_STOP, reason: Reason::Following(0) });
+        assert!(Driver::new(Profile::Aggressive, &config, 1.0).critical_gap < driver.critical_gap);
+    }
+
+    #[test]
+    fn drivers_wait_for_a_gap_in_cross_traffic() {
+        let config = HumanConfig::default();
+        let mut human = car("up_stright", 110.0); // Near the box
+        human.driver = Some(Driver::new(Profile::Cautious, &config, 1.0));
+        let near = car("right_stright", 100.0);
+        let far = car("right_stright", -60.0);
+        let same_approach = car("up_left", 0.0);
+
+        let seen = |cars: &[Car]| perceive(cars, &[], &[])[0];
+        assert_eq!(seen(&[human.clone()]).reason, Reason::Clear);
+        assert_eq!(seen(&[human.clone(), near]).reason, Reason::Yielding(1));
+        assert_eq!(seen(&[human.clone(), far]).reason, Reason::Clear);
+        assert_eq!(seen(&[human, same_approach]).reason, Reason::Clear);
+    }
+}
@@ -0,0 +1,34 @@
+// Kinematic power model used to estimate energy use and emissions per vehicle.
+// Tractive power at the wheels is computed from velocity and acceleration,
+// converted to fuel power through a fixed engine efficiency, and an idle
+// draw is always added so that time spent stopped still costs energy.
+
+const MASS: f32 = 1500.0; // kg
+const GRAVITY: f32 = 9.81; // m/s^2
+const ROLLING_RESISTANCE: f32 = 0.012;
+const AIR_DENSITY: f32 = 1.2; // kg/m^3
+const DRAG_AREA: f32 = 0.7; // Cd * frontal area, m^2
+const ENGINE_EFFICIENCY: f32 = 0.3;
+const IDLE_POWER: f32 = 2500.0; // W of fuel burnt with the engine idling
+
+const FUEL_ENERGY_DENSITY: f64 = 34.2e6; // J per litre of petrol
+const CO2_PER_LITRE: f64 = 2.31; // kg per litre of petrol
+
+// Fuel power in watts for a vehicle moving at `v` m/s and accelerating at
+// `a` m/s^2. Braking energy is not recovered.
+pub fn fuel_power(v: f32, a: f32) -> f32 {
+    let inertia = MASS * a * v;
+    let rolling = ROLLING_RESISTANCE * MASS * GRAVITY * v;
+    let drag = 0.5 * AIR_DENSITY * DRAG_AREA * v.powi(3);
+    let tractive = (inertia + rolling + drag).max(0.0);
+
+    IDLE_POWER + tractive / ENGINE_EFFICIENCY
+}
+
+pub fn fuel_litres(energy: f64) -> f64 {
+    energy / FUEL_ENERGY_DENSITY
+}
+
+pub fn co2_kg(energy: f64) -> f64 {
+    fuel_litres(energy) * CO2_PER_LITRE
+}
@@ -0,0 +1,381 @@
+use crate::cars::ROUTES;
+use crate::config::Config;
+use crate::controller::{Command, Policy, Reason, V_STOP};
+use crate::sim::{shift_index, Simulation, MAX_TICK};
+use crate::world::centre;
+use std::collections::HashSet;
+
+// Values per observed car: present flag, position relative to the
+// intersection centre (per 100 m), velocity (per cruise velocity), heading
+// (unit x, y) and the route one-hot in `ROUTES` order
+pub const CAR_FEATURES: usize = 6 + ROUTES.len();
+
+// Signal phases for `Action::Phase`: the approaches allowed into the box
+pub const PHASES: [&[&str]; 2] = [&["up", "down"], &["right", "left"]];
+
+// Cars of a red approach stop when their distance to the centre is between
+// the yield radius and this much further out; closer cars are let through
+const RED_ZONE: f32 = 30.0;
+
+// Ends an episode: no car moving faster than this for `DEADLOCK_SECONDS`
+const DEADLOCK_VELOCITY: f32 = 0.1;
+const DEADLOCK_SECONDS: f64 = 10.0;
+
+// Weights of the reward terms, per step:
+//   throughput * cars that left - delay * seconds of delay accrued
+//   - collision * new contacts between cars
+// Delay accrues at 1 s per second for queued cars and for stopped cars, and
+// proportionally for cars below cruise velocity, the running counterpart of
+// `Stats::average_delay`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reward {
+    pub throughput: f32,
+    pub delay: f32,
+    pub collision: f32,
+}
+
+impl Default for Reward {
+    fn default() -> Self {
+        Reward { throughput: 1.0, delay: 0.1, collision: 10.0 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EnvConfig {
+    pub sim: Config,
+    pub reward: Reward,
+    pub slots: usize,           // Cars observed, nearest to the centre first
+    pub ticks_per_step: usize,  // Controller ticks each action is held for
+    pub episode_seconds: f64,   // Simulated time before the episode is truncated
+    pub demand: f64,            // Random requests per minute
+    pub end_on_collision: bool,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            sim: Config::default(),
+            reward: Reward::default(),
+            slots: 16,
+            ticks_per_step: 6,
+            episode_seconds: 300.0,
+            demand: 20.0,
+            end_on_collision: true,
+        }
+    }
+}
+
+// What the agent controls during one step
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    // The built-in smart controller decides, as a baseline
+    Controller,
+    // A target velocity (m/s) per observation slot; cars not observed, and
+    // slots given a non-finite velocity, keep the smart controller's command
+    Velocities(Vec<f32>),
+    // Index into `PHASES`: the other approaches stop before the box; the
+    // smart controller still keeps following distances
+    Phase(usize),
+}
+
+// Fixed-size encoding: `slots * CAR_FEATURES` car values followed by the
+// spawn queue length of every route. `cars` maps each filled slot to its
+// index in `Simulation::cars`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub values: Vec<f32>,
+    pub cars: Vec<usize>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Info {
+    pub time: f64,
+    pub exits: usize,
+    pub collisions: usize,
+    pub delay: f32,
+    pub deadlocked: bool,
+    pub truncated: bool, // The episode ran out of time rather than failing
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+    pub info: Info,
+}
+
+// Gym-style environment around the intersection for training learned
+// controllers: `reset` starts a seeded episode, `step` applies an action for
+// `ticks_per_step` ticks and scores it.
+pub struct Env {
+    pub config: EnvConfig,
+    pub sim: Simulation,
+    contacts: HashSet<(usize, usize)>,
+    stuck_since: Option<f64>,
+    observed: Vec<usize>,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Self {
+        let mut env = Env {
+            config,
+            sim: Simulation::new(),
+            contacts: HashSet::new(),
+            stuck_since: None,
+            observed: Vec::new(),
+        };
+        env.reset(0);
+        env
+    }
+
+    pub fn observation_len(&self) -> usize {
+        self.config.slots * CAR_FEATURES + ROUTES.len()
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.sim = Simulation::with_seed(seed);
+        self.sim.set_config(self.config.sim);
+        self.sim.policy = Policy::External;
+        self.sim.demand = self.config.demand;
+        self.contacts.clear();
+        self.stuck_since = None;
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: &Action) -> Step {
+        let mut info = Info::default();
+        let mut done = false;
+        // Slots refer to the cars observed before the step
+        let mut slots: Vec<Option<usize>> = self.observed.iter().map(|&i| Some(i)).collect();
+
+        for _ in 0..self.config.ticks_per_step {
+            self.sim.commands = self.commands(action, &slots);
+            let removed = self.sim.tick(MAX_TICK);
+            for slot in slots.iter_mut() {
+                *slot = slot.and_then(|i| shift_index(i, &removed));
+            }
+            info.exits += removed.len();
+            info.collisions += self.new_contacts(&removed);
+            info.delay += self.delay_rate() * MAX_TICK;
+
+            if self.deadlocked() {
+                info.deadlocked = true;
+                done = true;
+                break;
+            }
+            if self.config.end_on_collision && info.collisions > 0 {
+                done = true;
+                break;
+            }
+        }
+        if !done && self.sim.now() >= self.config.episode_seconds {
+            info.truncated = true;
+            done = true;
+        }
+        info.time = self.sim.now();
+
+        let weights = &self.config.reward;
+        let reward = weights.throughput * info.exits as f32
+            - weights.delay * info.delay
+            - weights.collision * info.collisions as f32;
+        Step { observation: self.observe(), reward, done, info }
+    }
+
+    // The commands for the cars on the road this tick. Car indices are
+    // current: `slots` was shifted past earlier removals.
+    fn commands(&self, action: &Action, slots: &[Option<usize>]) -> Vec<Command> {
+        let config = &self.sim.config.controller;
+        let mut commands = Policy::Smart
+            .plan(&self.sim.cars, &self.sim.pedestrians, &self.sim.cyclists, config)
+            .expect("the smart policy plans every tick");
+        match action {
+            Action::Controller => {}
+            Action::Velocities(velocities) => {
+                for (slot, velocity) in slots.iter().zip(velocities).filter(|(_, velocity)| velocity.is_finite()) {
+                    if let Some(command) = slot.and_then(|i| commands.get_mut(i)) {
+                        *command = Command { velocity: velocity.clamp(V_STOP, config.cruise()), reason: Reason::External };
+                    }
+                }
+            }
+            Action::Phase(phase) => {
+                let green = PHASES.get(*phase).copied().unwrap_or(&[]);
+                let (cx, cy) = centre();
+                for (car, command) in self.sim.cars.iter().zip(commands.iter_mut()) {
+                    let approach = car.direction.split('_').next().unwrap_or("");
+                    let (x, y) = car.get_rect().center();
+                    let distance = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+                    let before_box = distance >= config.yield_radius && distance < config.yield_radius + RED_ZONE;
+                    if !green.contains(&approach) && before_box && !entered(car.direction.as_str(), x, y) {
+                        *command = Command { velocity: V_STOP, reason: Reason::External };
+                    }
+                }
+            }
+        }
+        commands
+    }
+
+    fn new_contacts(&mut self, removed: &[usize]) -> usize {
+        let previous: HashSet<(usize, usize)> = self
+            .contacts
+            .iter()
+            .filter_map(|&(i, j)| Some((shift_index(i, removed)?, shift_index(j, removed)?)))
+            .collect();
+        self.contacts = self.sim.collisions().into_iter().collect();
+        self.contacts.difference(&previous).count()
+    }
+
+    // Seconds of delay accrued per second, over all cars and queues
+    fn delay_rate(&self) -> f32 {
+        let cruise = self.sim.config.controller.cruise();
+        let driving: f32 = self.sim.cars.iter().map(|car| (1.0 - car.velocity / cruise).max(0.0)).sum();
+        driving + self.sim.queued() as f32
+    }
+
+    fn deadlocked(&mut self) -> bool {
+        let moving = self.sim.cars.iter().any(|car| car.velocity > DEADLOCK_VELOCITY);
+        if self.sim.cars.is_empty() || moving {
+            self.stuck_since = None;
+            return false;
+        }
+        let since = *self.stuck_since.get_or_insert(self.sim.now());
+        self.sim.now() - since >= DEADLOCK_SECONDS
+    }
+
+    fn observe(&mut self) -> Observation {
+        let (cx, cy) = centre();
+        let cruise = self.sim.config.controller.cruise();
+        let mut order: Vec<(f32, usize)> = self
+            .sim
+            .cars
+            .iter()
+            .enumerate()
+            .map(|(i, car)| {
+                let (x, y) = car.get_rect().center();
+                ((x - cx).powi(2) + (y - cy).powi(2), i)
+            })
+            .collect();
+        order.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        order.truncate(self.config.slots);
+
+        let mut values = vec![0.0; self.observation_len()];
+        for (slot, &(_, i)) in order.iter().enumerate() {
+            let car = &self.sim.cars[i];
+            let (x, y) = car.get_rect().center();
+            let (hx, hy) = car.current_heading();
+            let features = &mut values[slot * CAR_FEATURES..(slot + 1) * CAR_FEATURES];
+            features[..6].copy_from_slice(&[1.0, (x - cx) / 100.0, (y - cy) / 100.0, car.velocity / cruise, hx, hy]);
+            if let Some(route) = ROUTES.iter().position(|r| *r == car.direction) {
+                features[6 + route] = 1.0;
+            }
+        }
+        let queues = self.config.slots * CAR_FEATURES;
+        for (lane, queue) in self.sim.queues.iter().enumerate() {
+            values[queues + lane] = queue.len() as f32;
+        }
+
+        self.observed = order.iter().map(|&(_, i)| i).collect();
+        Observation { values, cars: self.observed.clone() }
+    }
+}
+
+// Whether a car has already crossed its stop line into the box; the red
+// zone only holds cars that have not
+fn entered(direction: &str, x: f32, y: f32) -> bool {
+    let (cx, cy) = centre();
+    match direction.split('_').next().unwrap_or("") {
+        "up" => y < cy,
+        "down" => y > cy,
+        "right" => x > cx,
+        "left" => x < cx,
+        _ => false,
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Self::new(EnvConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(env: &mut Env, action: &Action, steps: usize) -> (f32, Info) {
+        let mut total = 0.0;
+        let mut last = Info::default();
+        for _ in 0..steps {
+            let step = env.step(action);
+            assert_eq!(step.observation.values.len(), env.observation_len());
+            total += step.reward;
+            last = step.info;
+            if step.done {
+                break;
+            }
+        }
+        (total, last)
+    }
+
+    #[test]
+    fn episodes_are_reproducible_from_the_seed() {
+        let mut a = Env::default();
+        let mut b = Env::default();
+        assert_eq!(a.reset(5), b.reset(5));
+        for _ in 0..200 {
+            assert_eq!(a.step(&Action::Phase(0)), b.step(&Action::Phase(0)));
+        }
+    }
+
+    #[test]
+    fn controller_action_earns_throughput_without_collisions() {
+        let mut env = Env::new(EnvConfig { demand: 10.0, episode_seconds: 60.0, ..Default::default() });
+        env.reset(2);
+        let (reward, info) = run(&mut env, &Action::Controller, 1000);
+        assert!(info.truncated && info.collisions == 0, "{:?}", info);
+        assert!(reward > 0.0, "reward {}", reward);
+    }
+
+    #[test]
+    fn stopping_everyone_is_penalised_for_delay() {
+        let mut env = Env::new(EnvConfig { demand: 30.0, ..Default::default() });
+        env.reset(1);
+        env.sim.request("up_stright");
+        let stop = Action::Velocities(vec![0.0; env.config.slots]);
+        let first = env.step(&stop);
+        assert_eq!(first.observation.cars.len(), 1);
+        // Present, 6 m east of the centre (the northbound straight lane)
+        assert_eq!(&first.observation.values[..2], &[1.0, 0.06]);
+        let (reward, info) = run(&mut env, &stop, 200);
+        assert_eq!(env.sim.stats.nbr_passed, 0);
+        assert!(info.delay > 0.0 && reward < 0.0);
+    }
+
+    #[test]
+    fn non_finite_velocities_leave_cars_to_the_controller() {
+        let mut env = Env::new(EnvConfig { demand: 0.0, ..Default::default() });
+        env.reset(0);
+        env.sim.request("up_stright");
+        let mut velocities = vec![f32::NAN; env.config.slots];
+        env.step(&Action::Velocities(velocities.clone()));
+        velocities[0] = f32::INFINITY;
+        env.step(&Action::Velocities(velocities));
+        let car = &env.sim.cars[0];
+        assert_eq!(env.sim.commands[0].reason, Reason::Clear);
+        assert_eq!(car.velocity, env.sim.config.controller.cruise());
+    }
+
+    #[test]
+    fn red_phase_holds_cars_before_the_box() {
+        let mut env = Env::new(EnvConfig { demand: 0.0, ..Default::default() });
+        env.reset(0);
+        env.sim.request("right_stright");
+        // Horizontal approaches are red under phase 0
+        run(&mut env, &Action::Phase(0), 100);
+        let car = &env.sim.cars[0];
+        let (x, _) = car.get_rect().center();
+        assert!(car.velocity == 0.0 && x < centre().0 - env.sim.config.controller.yield_radius);
+        run(&mut env, &Action::Phase(1), 100);
+        assert_eq!(env.sim.stats.nbr_passed, 1);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::cars::VehicleId;
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+// Lifecycle of a vehicle as discrete events, emitted by `Simulation` as they
+// happen. Logging, statistics and replay hang off these instead of diffing
+// the car list between ticks.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    Spawned,
+    EnteredBox, // The body started to overlap the intersection box
+    ExitedBox,
+    Stopped,
+    Resumed,
+    Despawned, // Left the world, or was cleared
+    Yielded { to: VehicleId }, // Started giving way to another car
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub time: f64,
+    pub vehicle: VehicleId,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+// The events of the latest tick, plus the channels of subscribed observers.
+// A cloned simulation starts without subscribers: its events are its own.
+#[derive(Default)]
+pub struct Events {
+    recent: Vec<Event>,
+    seen: usize, // Events emitted before the latest tick ended
+    subscribers: Vec<Sender<Event>>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Events { recent: Vec::new(), seen: 0, subscribers: Vec::new() }
+    }
+
+    // Every event from now on, in order. Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    // Events of the latest tick, and of any spawns requested since
+    pub fn recent(&self) -> &[Event] {
+        &self.recent
+    }
+
+    pub(crate) fn emit(&mut self, time: f64, vehicle: VehicleId, kind: EventKind) {
+        let event = Event { time, vehicle, kind };
+        self.recent.push(event);
+        self.subscribers.retain(|subscriber| subscriber.send(event).is_ok());
+    }
+
+    // Forgets the events reported by the previous tick
+    pub(crate) fn start_tick(&mut self) {
+        self.recent.drain(..self.seen);
+    }
+
+    pub(crate) fn end_tick(&mut self) {
+        self.seen = self.recent.len();
+    }
+}
+
+impl Clone for Events {
+    fn clone(&self) -> Self {
+        Events { recent: self.recent.clone(), seen: self.seen, subscribers: Vec::new() }
+    }
+}
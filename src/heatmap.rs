@@ -0,0 +1,225 @@
+use crate::cars::Car;
+use crate::spatial::{Grid, Rect};
+use crate::world::*;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+const CELL: f32 = 0.5; // Metres per heatmap cell
+const COLS: usize = (WORLD_WIDTH / CELL) as usize;
+const ROWS: usize = (WORLD_HEIGHT / CELL) as usize;
+
+// Slower than this counts as stopped
+const STOPPED_VELOCITY: f32 = 0.5;
+
+// Grid cells for the conflict pass, about a radar's reach as in the
+// controller's neighbour search
+const CONFLICT_CELL: f32 = 16.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Layer {
+    Stopped,
+    Conflicts,
+}
+
+impl Layer {
+    fn name(&self) -> &'static str {
+        match self {
+            Layer::Stopped => "stopped",
+            Layer::Conflicts => "conflicts",
+        }
+    }
+}
+
+// Seconds accumulated per world cell: time cars spent stopped there, and time
+// two cars' radars overlapped there. Conflict time is also tallied per pair
+// of routes, which points at the movements causing the congestion.
+pub struct Heatmap {
+    stopped: Vec<f32>,
+    conflicts: Vec<f32>,
+    pairs: HashMap<(String, String), f32>,
+    texture: Option<(Layer, Texture2D)>,
+    last_refresh: f64,
+}
+
+fn cell_index((x, y): (f32, f32)) -> Option<usize> {
+    if x < 0.0 || y < 0.0 {
+        return None;
+    }
+    let col = (x / CELL) as usize;
+    let row = (y / CELL) as usize;
+    if col < COLS && row < ROWS {
+        Some(row * COLS + col)
+    } else {
+        None
+    }
+}
+
+fn heat_color(value: f32, max: f32) -> Color {
+    if value <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0, 0.0);
+    }
+    // Log scale so that a few very long stops do not wash out the rest
+    let t = (value.ln_1p() / max.ln_1p()).min(1.0);
+    Color::new(1.0, 1.0 - t, 0.0, 0.15 + 0.65 * t)
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Heatmap {
+            stopped: vec![0.0; COLS * ROWS],
+            conflicts: vec![0.0; COLS * ROWS],
+            pairs: HashMap::new(),
+            texture: None,
+            last_refresh: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, dt: f32, cars: &[Car]) {
+        for car in cars {
+            if car.velocity < STOPPED_VELOCITY {
+                if let Some(k) = cell_index(car.get_rect().center()) {
+                    self.stopped[k] += dt;
+                }
+            }
+        }
+
+        // Same conflict test as the controller's cross-traffic rule, only
+        // between cars whose radars share a grid cell
+        let radars: Vec<Rect> = cars.iter().map(Car::get_radar).collect();
+        let rects: Vec<Rect> = cars.iter().map(Car::get_rect).collect();
+        let grid = Grid::new(&radars, CONFLICT_CELL);
+        let mut candidates = Vec::new();
+        for i in 0..cars.len() {
+            grid.query(radars[i], &mut candidates);
+            for &j in candidates.iter().filter(|&&j| j > i) {
+                if rects[i].intersect(rects[j]).is_some() {
+                    continue;
+                }
+                if let Some(overlap) = radars[i].intersect(radars[j]) {
+                    if let Some(k) = cell_index(overlap.center()) {
+                        self.conflicts[k] += dt;
+                    }
+                    let (a, b) = (&cars[i].direction, &cars[j].direction);
+                    let key = if a <= b { (a.clone(), b.clone()) } else { (b.clone(), a.clone()) };
+                    *self.pairs.entry(key).or_insert(0.0) += dt;
+                }
+            }
+        }
+    }
+
+    fn cells(&self, layer: Layer) -> &[f32] {
+        match layer {
+            Layer::Stopped => &self.stopped,
+            Layer::Conflicts => &self.conflicts,
+        }
+    }
+
+    fn image(&self, layer: Layer) -> Image {
+        let cells = self.cells(layer);
+        let max = cells.iter().copied().fold(0.0, f32::max);
+        let mut image = Image::gen_image_color(COLS as u16, ROWS as u16, Color::new(0.0, 0.0, 0.0, 0.0));
+        for (k, value) in cells.iter().enumerate() {
+            image.set_pixel((k % COLS) as u32, (k / COLS) as u32, heat_color(*value, max));
+        }
+        image
+    }
+
+    // Route pairs with the most conflict time, worst first
+    pub fn worst_pairs(&self, count: usize) -> Vec<(&(String, String), f32)> {
+        let mut pairs: Vec<_> = self.pairs.iter().map(|(key, time)| (key, *time)).collect();
+        pairs.sort_by(|a, b| b.1.total_cmp(&a.1));
+        pairs.truncate(count);
+        pairs
+    }
+
+    // Drawn in world coordinates. The texture is rebuilt at most twice a
+    // second since regenerating it every frame is wasted work.
+    pub fn draw(&mut self, layer: Layer) {
+        let stale = match &self.texture {
+            Some((drawn, _)) => *drawn != layer || get_time() - self.last_refresh > 0.5,
+            None => true,
+        };
+        if stale {
+            let texture = Texture2D::from_image(&self.image(layer));
+            texture.set_filter(FilterMode::Nearest);
+            self.texture = Some((layer, texture));
+            self.last_refresh = get_time();
+        }
+        if let Some((_, texture)) = &self.texture {
+            draw_texture_ex(
+                texture, 0.0, 0.0, WHITE,
+                DrawTextureParams { dest_size: Some(vec2(WORLD_WIDTH, WORLD_HEIGHT)), ..Default::default() },
+            );
+        }
+    }
+
+    // Legend drawn in screen space: the layer name and the worst route pairs
+    pub fn draw_legend(&self, layer: Layer) {
+        let x = screen_width() / 2.0 - 170.0;
+        let y = 10.0;
+        let worst = self.worst_pairs(5);
+        draw_rectangle(x, y, 340.0, 40.0 + worst.len() as f32 * 22.0, Color::from_rgba(0, 0, 0, 200));
+        draw_text(&format!("HEATMAP: {}", layer.name()), x + 10.0, y + 26.0, 22.0, ORANGE);
+        for (n, ((a, b), time)) in worst.iter().enumerate() {
+            draw_text(&format!("{} x {}: {:.1} s", a, b, time), x + 10.0, y + 50.0 + n as f32 * 22.0, 18.0, WHITE);
+        }
+    }
+
+    // Writes both layers to PNG files in the working directory and returns
+    // their paths. `Image::export_png` panics on write errors, so each file is
+    // created first to report those instead.
+    pub fn export_png(&self) -> std::io::Result<Vec<String>> {
+        [Layer::Stopped, Layer::Conflicts]
+            .iter()
+            .map(|layer| {
+                let path = format!("heatmap_{}.png", layer.name());
+                std::fs::File::create(&path)?;
+                // `Image::export_png` flips rows for render targets, so flip
+                // first to keep north at the top of the file
+                let image = self.image(*layer);
+                let mut flipped = image.clone();
+                for row in 0..ROWS {
+                    for col in 0..COLS {
+                        let color = image.get_pixel(col as u32, row as u32);
+                        flipped.set_pixel(col as u32, (ROWS - 1 - row) as u32, color);
+                    }
+                }
+                flipped.export_png(&path);
+                Ok(path)
+            })
+            .collect()
+    }
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cars::stress_layout;
+
+    #[test]
+    fn grid_conflicts_match_every_pair() {
+        let cars = stress_layout(240, 3);
+        let mut heatmap = Heatmap::new();
+        heatmap.record(1.0, &cars);
+
+        let mut conflicts = vec![0.0; COLS * ROWS];
+        for i in 0..cars.len() {
+            for j in i + 1..cars.len() {
+                if cars[i].get_rect().intersect(cars[j].get_rect()).is_some() {
+                    continue;
+                }
+                if let Some(k) = cars[i].get_radar().intersect(cars[j].get_radar()).and_then(|o| cell_index(o.center())) {
+                    conflicts[k] += 1.0;
+                }
+            }
+        }
+        assert!(conflicts.iter().any(|time| *time > 0.0));
+        assert_eq!(heatmap.conflicts, conflicts);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::cars::Car;
+use crate::controller::Command;
+use crate::world::*;
+use macroquad::prelude::*;
+
+// Panel describing the selected car, drawn in screen space in the top-right
+// corner. `cars` are all the cars, to name the ones the command refers to.
+pub fn draw_inspector(car: &Car, command: &Command, cars: &[Car]) {
+    let w = 320.0;
+    let x = screen_width() - w - 10.0;
+    let y = 10.0;
+    draw_rectangle(x, y, w, 246.0, Color::from_rgba(0, 0, 0, 200));
+    draw_text(&format!("CAR {}", car.id), x + 15.0, y + 32.0, 28.0, YELLOW);
+
+    let time = if car.time.is_finite() { format!("{:.2} s", car.time) } else { "stopped".to_string() };
+    let driver = match &car.driver {
+        Some(driver) => format!("Driver: human, {} ({:.1} s reaction)", driver.profile.name(), driver.reaction_time),
+        None => "Driver: connected AV".to_string(),
+    };
+    let lines = [
+        format!("Route: {}", car.direction),
+        driver,
+        format!("Velocity: {:.1} km/h", to_kmh(car.velocity)),
+        format!("Target: {:.1} km/h", to_kmh(car.target_velocity)),
+        format!("Distance to exit: {:.1} m", car.distance),
+        format!("Time to exit: {}", time),
+        format!("Command: {}", command.describe(cars)),
+    ];
+    for (n, line) in lines.iter().enumerate() {
+        draw_text(line, x + 15.0, y + 65.0 + n as f32 * 26.0, 20.0, WHITE);
+    }
+}
@@ -0,0 +1,58 @@
+// Smart intersection simulator as a library. The core (cars, routes,
+// geometry, the controller, statistics and the headless `Simulation`) has no
+// windowing dependency and can be embedded in other tools:
+//
+//     let mut sim = traffic_sim::Simulation::with_seed(1);
+//     sim.request("up_left");
+//     sim.advance(10.0, |_, _, _| {});
+//
+// The macroquad front-end (drawing, camera, input and panels) is behind the
+// default `render` feature; the binary in `main.rs` is a thin loop over it.
+pub mod batch;
+pub mod cars;
+pub mod clock;
+pub mod config;
+pub mod control;
+pub mod controller;
+pub mod cyclists;
+pub mod drivers;
+pub mod energy;
+pub mod env;
+pub mod events;
+pub mod pedestrians;
+#[cfg(feature = "python")]
+mod python;
+pub mod rng;
+pub mod sim;
+pub mod spatial;
+pub mod stats;
+#[cfg(test)]
+mod trace;
+pub mod world;
+
+#[cfg(feature = "render")]
+pub mod camera;
+#[cfg(feature = "render")]
+pub mod charts;
+#[cfg(feature = "render")]
+mod dashed;
+#[cfg(feature = "render")]
+pub mod debug;
+#[cfg(feature = "render")]
+pub mod draw_road;
+#[cfg(feature = "render")]
+pub mod heatmap;
+#[cfg(feature = "render")]
+pub mod inspector;
+#[cfg(feature = "render")]
+pub mod playback;
+#[cfg(feature = "render")]
+pub mod tuning;
+
+pub use cars::{lane_at, spawn_point, Car, VehicleId, ROUTES};
+pub use config::{Config, ConfigError};
+pub use controller::{plan, Command, Kind, Policy, Reason};
+pub use events::{Event, EventKind, Events};
+pub use sim::{Simulation, MAX_TICK};
+pub use spatial::Rect;
+pub use stats::Stats;
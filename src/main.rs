@@ -1,302 +1,359 @@
 use macroquad::prelude::*;
-mod cars;
-mod dashed;
-mod draw_road;
+use traffic_sim::{camera, cars, charts, config, control, cyclists, debug, draw_road, energy, heatmap, inspector, pedestrians, playback, sim, stats, tuning, world};
+use camera::*;
 use cars::*;
+use charts::*;
+use config::*;
+use control::*;
+use cyclists::*;
+use debug::*;
 use draw_road::*;
+use heatmap::*;
+use inspector::*;
+use pedestrians::*;
+use playback::*;
+use sim::*;
+use stats::*;
+use tuning::*;
+use world::*;
 
-#[derive(Clone, Debug)]
-pub struct Stats {
-    pub total_cars: u32,
-    pub nbr_passed: u32,
-    pub max_velocity: f32,
-    pub min_velocity: f32,
-    pub average_velocity: f32,
-    pub max_time: f32,
-    pub min_time: f32,
-    pub total_velocity_sum: f64,
-    pub velocity_samples: u64,
+fn window_conf() -> Conf {
+    Conf {
+        window_title: "Smart AV Intersection".to_string(),
+        window_width: WINDOW_WIDTH,
+        window_height: WINDOW_HEIGHT,
+        window_resizable: true,
+        fullscreen: false,
+        ..Default::default()
+    }
 }
 
-impl Stats {
-    pub fn new() -> Self {
-        Stats {
-            total_cars: 0,
-            nbr_passed: 0,
-            max_velocity: f32::MIN,
-            min_velocity: f32::MAX,
-            average_velocity: 0.0,
-            max_time: f32::MIN,
-            min_time: f32::MAX,
-            total_velocity_sum: 0.0,
-            velocity_samples: 0,
-        }
+// Human-driven cars are tinted orange
+fn draw_cars(car_tex: &Texture2D, cars: &[Car], selected: Option<usize>) {
+    for car in cars {
+        let tint = if car.connected() { WHITE } else { Color::from_rgba(255, 170, 90, 255) };
+        draw_texture_ex(
+            car_tex,
+            car.cord.0,
+            car.cord.1,
+            tint,
+            DrawTextureParams {
+                dest_size: Some(vec2(car.width, car.height)),
+                rotation: car.rotation.to_radians(),
+                ..Default::default()
+            },
+        );
     }
 
-    pub fn sample_velocity(&mut self, velocity: f32) {
-        if velocity > self.max_velocity { self.max_velocity = velocity; }
-        if velocity < self.min_velocity { self.min_velocity = velocity; }
-        self.total_velocity_sum += velocity as f64;
-        self.velocity_samples += 1;
-        self.average_velocity = (self.total_velocity_sum / self.velocity_samples as f64) as f32;
+    if let Some(car) = selected.and_then(|i| cars.get(i)) {
+        let rect = car.get_rect();
+        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 0.4, YELLOW);
     }
+}
 
-    pub fn register_passed_car(&mut self, time_lived: f32) {
-        self.nbr_passed += 1;
-        if time_lived > self.max_time { self.max_time = time_lived; }
-        if time_lived < self.min_time { self.min_time = time_lived; }
+// Pedestrians as dots: orange while waiting at the curb, white walking
+fn draw_pedestrians(pedestrians: &[Pedestrian]) {
+    for pedestrian in pedestrians {
+        let (x, y) = pedestrian.get_rect().center();
+        let color = if pedestrian.walking() { WHITE } else { ORANGE };
+        draw_circle(x, y, PEDESTRIAN_SIZE / 2.0, color);
     }
 }
 
-fn window_conf() -> Conf {
-    Conf {
-        window_title: "Smart AV Intersection".to_string(),
-        window_width: 1100,
-        window_height: 800,
-        window_resizable: false,
-        fullscreen: false,
-        ..Default::default()
+// Cyclists as small bars along their lane: green riding, orange stopped
+fn draw_cyclists(cyclists: &[Cyclist]) {
+    for cyclist in cyclists {
+        let rect = cyclist.get_rect();
+        let color = if cyclist.velocity > 0.0 { LIME } else { ORANGE };
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
     }
 }
 
-fn can_spawn(cars: &Vec<Car>, spawn_cord: (f32, f32)) -> bool {
-    let safe_dist = 220.0;
-    for car in cars {
-        let dist = ((car.cord.0 - spawn_cord.0).powi(2) + (car.cord.1 - spawn_cord.1).powi(2)).sqrt();
-        if dist < safe_dist {
-            return false;
+// Number of cars waiting in each lane's spawn queue, drawn where the lane
+// enters the world, or at the screen edge when the entry is off-screen.
+fn draw_spawn_queues(view: &View, sim: &Simulation) {
+    let camera = view.camera();
+    for (lane, direction) in ROUTES.iter().enumerate() {
+        let waiting = sim.queues[lane].len();
+        let Some(((x, y), _)) = spawn_point(direction) else {
+            continue;
+        };
+        if waiting == 0 {
+            continue;
         }
+        let entry = camera.world_to_screen(vec2(x + CAR_WIDTH / 2.0, y + CAR_LENGTH / 2.0));
+        let pos = vec2(entry.x.clamp(10.0, screen_width() - 50.0), entry.y.clamp(30.0, screen_height() - 60.0));
+        draw_rectangle(pos.x - 4.0, pos.y - 18.0, 48.0, 24.0, Color::from_rgba(180, 30, 30, 220));
+        draw_text(&format!("+{}", waiting), pos.x, pos.y, 22.0, WHITE);
     }
-    true
 }
 
+// Rows of the statistics panel are this far apart, so that the panel and
+// its close hint fit the default window
+const STATS_ROW: f32 = 30.0;
+
+fn draw_statistics(stats: &Stats) {
+    let cx = screen_width() / 2.0;
+    let cy = screen_height() / 2.0;
+    let height = WINDOW_HEIGHT as f32 - 40.0;
+    let top = cy - height / 2.0;
+    draw_rectangle(cx - 230.0, top, 460.0, height, Color::from_rgba(0, 0, 0, 220));
+    draw_text("STATISTICS", cx - 85.0, top + 50.0, 35.0, WHITE);
+
+    let min_v = if stats.min_velocity == f32::MAX { 0.0 } else { stats.min_velocity };
+    let max_v = if stats.max_velocity == f32::MIN { 0.0 } else { stats.max_velocity };
+    let min_t = if stats.min_time == f32::MAX { 0.0 } else { stats.min_time };
+    let max_t = if stats.max_time == f32::MIN { 0.0 } else { stats.max_time };
+    let min_e = if stats.min_energy == f64::MAX { 0.0 } else { stats.min_energy };
+    let max_e = if stats.max_energy == f64::MIN { 0.0 } else { stats.max_energy };
+    let avg_queue = if stats.queued_cars == 0 { 0.0 } else { stats.total_queue_wait / stats.queued_cars as f64 };
+
+    let rows = [
+        format!("Total Cars: {}", stats.total_cars),
+        format!("Passed Cars: {}", stats.nbr_passed),
+        format!("Average Velocity: {:.1} km/h", to_kmh(stats.average_velocity)),
+        format!("Max Velocity: {:.1} km/h", to_kmh(max_v)),
+        format!("Min Velocity: {:.1} km/h", to_kmh(min_v)),
+        format!("Max Time Lived: {:.2} s", max_t),
+        format!("Min Time Lived: {:.2} s", min_t),
+        format!("Distance Driven: {:.0} m", stats.total_distance),
+        format!("Energy per Trip: {:.1} kJ", stats.average_energy / 1000.0),
+        format!("Max / Min Trip: {:.1} / {:.1} kJ", max_e / 1000.0, min_e / 1000.0),
+        format!("Queued Cars: {}", stats.queued_cars),
+        format!("Queue Wait: {:.1} s (max {:.1})", avg_queue, stats.max_queue_wait),
+        format!("Total Fuel: {:.3} L", energy::fuel_litres(stats.total_energy)),
+        format!("CO2: {:.2} kg", energy::co2_kg(stats.total_energy)),
+        format!("Pedestrians Crossed: {}", stats.pedestrians_crossed),
+        format!("Pedestrian Wait: {:.1} s (max {:.1})", stats.average_pedestrian_wait, stats.max_pedestrian_wait),
+        format!("Cyclists Passed: {}", stats.cyclists_passed),
+        format!("Cyclist Delay: {:.1} s (max {:.1})", stats.average_cyclist_delay, stats.max_cyclist_delay),
+        format!("Cyclist Near Misses: {}", stats.cyclist_near_misses),
+    ];
+    for (k, row) in rows.iter().enumerate() {
+        draw_text(row, cx - 190.0, top + 100.0 + k as f32 * STATS_ROW, 22.0, WHITE);
+    }
+
+    draw_text("Press ESC again to close", cx - 100.0, top + height - 25.0, 20.0, YELLOW);
+}
+
+// Value following `flag` on the command line
+fn arg(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1).cloned())
+}
+
+// Tuning file given with `--config PATH`, `traffic.toml` otherwise
+fn config_path() -> String {
+    arg("--config").unwrap_or_else(|| "traffic.toml".to_string())
+}
+
+// Seconds a notice (config reload, heatmap export) stays on screen
+const NOTICE_SECONDS: f64 = 4.0;
+
 #[macroquad::main(window_conf)]
 async fn main() {
     let car_tex: Texture2D = load_texture("assets/car2.png").await.unwrap();
     car_tex.set_filter(FilterMode::Nearest);
-    let mut cars: Vec<Car> = Vec::new();
-    let mut stats = Stats::new();
+    let mut sim = Simulation::with_seed(miniquad::date::now().to_bits());
+    let mut playback = Playback::new();
     let mut show_stats = false;
+    let mut view = View::new();
+    let mut selected: Option<VehicleId> = None;
+    let mut show_debug = false;
+    let mut show_charts = false;
+    let mut history = History::new();
+    let mut heatmap = Heatmap::new();
+    let mut heat_layer: Option<Layer> = None;
+
+    let mut tuning = Tuning::new();
+    let mut watcher = ConfigWatcher::new(config_path());
+    // A missing file just means the built-in values
+    let mut notice: Option<(String, Color, f64)> = None;
+    if watcher.path().exists() {
+        match Config::load(watcher.path()) {
+            Ok(config) => tuning.apply(&mut sim, config),
+            Err(err) => notice = Some((format!("{}: {}", watcher.path().display(), err), RED, get_time())),
+        }
+    }
 
-    // Smart Intersection Velocity Constants
-    const V_CRUISE: f32 = 400.0;
-    const V_ADJUST: f32 = 250.0;
-    const V_CRAWL: f32 = 100.0;
-    const V_STOP: f32 = 0.0;
-    const SAFETY_DISTANCE: f32 = 150.0; // Strictly positive safety distance
+    // External controllers connect here with `--listen ADDR` (see control.rs)
+    let mut server = arg("--listen").map(|addr| match ControlServer::bind(&addr) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("cannot listen on {}: {}", addr, err);
+            std::process::exit(1);
+        }
+    });
 
     loop {
-        let dt = get_frame_time();
-
-        if is_key_pressed(KeyCode::Escape) {
-            show_stats = !show_stats;
+        if let Some(server) = server.as_mut() {
+            server.handle(&mut sim, &mut playback.paused);
+            // Nothing recorded so far belongs to the fresh run
+            if server.reset_applied() {
+                history = History::new();
+                heatmap = Heatmap::new();
+                tuning.restart(&sim);
+                selected = None;
+            }
         }
 
-        if show_stats {
-            clear_background(Color::from_rgba(4, 96, 85, 255));
-            draw_road();
-            
-            for car in &cars {
-                draw_texture_ex(
-                    &car_tex, car.cord.0, car.cord.1, WHITE,
-                    DrawTextureParams {
-                        dest_size: Some(vec2(car.width as f32, car.height as f32)),
-                        rotation: car.rotation.to_radians(),
-                        ..Default::default()
-                    },
-                );
+        match watcher.poll() {
+            Some(Ok(config)) => {
+                tuning.apply(&mut sim, config);
+                notice = Some((format!("Reloaded {}", watcher.path().display()), GREEN, get_time()));
             }
-
-            let cx = screen_width() / 2.0;
-            let cy = screen_height() / 2.0;
-            draw_rectangle(cx - 200.0, cy - 220.0, 400.0, 440.0, Color::from_rgba(0, 0, 0, 220));
-            draw_text("STATISTICS", cx - 85.0, cy - 160.0, 35.0, WHITE);
-
-            let min_v = if stats.min_velocity == f32::MAX { 0.0 } else { stats.min_velocity };
-            let max_v = if stats.max_velocity == f32::MIN { 0.0 } else { stats.max_velocity };
-            let min_t = if stats.min_time == f32::MAX { 0.0 } else { stats.min_time };
-            let max_t = if stats.max_time == f32::MIN { 0.0 } else { stats.max_time };
-
-            let text_x = cx - 160.0;
-            draw_text(&format!("Total Cars: {}", stats.total_cars), text_x, cy - 100.0, 24.0, WHITE);
-            draw_text(&format!("Passed Cars: {}", stats.nbr_passed), text_x, cy - 60.0, 24.0, WHITE);
-            draw_text(&format!("Average Velocity: {:.2} px/s", stats.average_velocity), text_x, cy - 20.0, 24.0, WHITE);
-            draw_text(&format!("Max Velocity: {:.2} px/s", max_v), text_x, cy + 20.0, 24.0, WHITE);
-            draw_text(&format!("Min Velocity: {:.2} px/s", min_v), text_x, cy + 60.0, 24.0, WHITE);
-            draw_text(&format!("Max Time Lived: {:.2} s", max_t), text_x, cy + 100.0, 24.0, WHITE);
-            draw_text(&format!("Min Time Lived: {:.2} s", min_t), text_x, cy + 140.0, 24.0, WHITE);
-            
-            draw_text("Press ESC again to exit", cx - 100.0, cy + 195.0, 20.0, YELLOW);
-
-            next_frame().await;
-            continue;
+            // Keep running with the previous values until the file is fixed
+            Some(Err(err)) => notice = Some((format!("{}: {}", watcher.path().display(), err), RED, get_time())),
+            None => {}
         }
 
-        clear_background(Color::from_rgba(4, 96, 85, 255));
-        draw_road();
+        // The statistics screen is an overlay only; pausing is separate (Space)
+        if is_key_pressed(KeyCode::Escape) {
+            show_stats = !show_stats;
+        }
+        playback.handle_input();
 
         if is_key_pressed(KeyCode::C) || is_key_pressed(KeyCode::Backspace) {
-            cars.clear();
+            sim.clear();
+            selected = None;
         }
 
-        if is_key_pressed(KeyCode::Up) {
-            let random_case = rand::gen_range(0, 3);
-            let (cord, direction) = match random_case {
-                0 => ((screen_width() / 2.0 + 85.0, screen_height() - 55.0), "up_right"),
-                1 => ((screen_width() / 2.0 + 45.0, screen_height() - 55.0), "up_stright"),
-                _ => ((screen_width() / 2.0 + 5.0, screen_height() - 55.0), "up_left"),
+        if is_key_pressed(KeyCode::Tab) && !sim.cars.is_empty() {
+            let next = selected.and_then(|id| sim.index_of(id)).map_or(0, |i| (i + 1) % sim.cars.len());
+            selected = Some(sim.cars[next].id);
+        }
+        if is_key_pressed(KeyCode::F) && selected.is_some() {
+            view.following = !view.following;
+        }
+        if is_key_pressed(KeyCode::Home) {
+            view.reset();
+        }
+        if is_key_pressed(KeyCode::D) {
+            show_debug = !show_debug;
+        }
+        if is_key_pressed(KeyCode::G) {
+            show_charts = !show_charts;
+        }
+        if is_key_pressed(KeyCode::T) {
+            tuning.open = !tuning.open;
+        }
+        if is_key_pressed(KeyCode::H) {
+            heat_layer = match heat_layer {
+                None => Some(Layer::Stopped),
+                Some(Layer::Stopped) => Some(Layer::Conflicts),
+                Some(Layer::Conflicts) => None,
             };
-            if can_spawn(&cars, cord) {
-                cars.push(Car::new(direction.to_string(), 30, 50, cord, 0.0));
-                stats.total_cars += 1;
-            }
+        }
+        if is_key_pressed(KeyCode::X) {
+            notice = Some(match heatmap.export_png() {
+                Ok(paths) => (format!("Heatmap exported to {}", paths.join(", ")), GREEN, get_time()),
+                Err(err) => (format!("Heatmap export failed: {}", err), RED, get_time()),
+            });
         }
 
-        if is_key_pressed(KeyCode::Right) {
-            let random_case = rand::gen_range(0, 3);
-            let (cord, direction) = match random_case {
-                0 => ((10.0, screen_height() / 2.0 + 75.0), "right_right"),
-                1 => ((10.0, screen_height() / 2.0 + 35.0), "right_stright"),
-                _ => ((10.0, screen_height() / 2.0 - 5.0), "right_left"),
-            };
-            if can_spawn(&cars, cord) {
-                cars.push(Car::new(direction.to_string(), 30, 50, cord, 90.0));
-                stats.total_cars += 1;
+        // Left click selects the car under the cursor, or spawns a car in the
+        // approach lane under it (the lane decides the maneuver)
+        if is_mouse_button_pressed(MouseButton::Left) && !tuning.wants_mouse() {
+            let point = view.screen_to_world(Vec2::from(mouse_position()));
+            if let Some(i) = sim.cars.iter().position(|car| car.get_rect().contains((point.x, point.y))) {
+                selected = Some(sim.cars[i].id);
+            } else if let Some((direction, cord, rotation)) = lane_at((point.x, point.y)) {
+                sim.spawn(direction, cord, rotation);
+            } else {
+                selected = None;
+                view.following = false;
             }
         }
 
+        if is_key_pressed(KeyCode::Up) {
+            sim.request_random(&ROUTES[0..3]);
+        }
+        if is_key_pressed(KeyCode::Right) {
+            sim.request_random(&ROUTES[3..6]);
+        }
         if is_key_pressed(KeyCode::Down) {
-            let random_case = rand::gen_range(0, 3);
-            let (cord, direction) = match random_case {
-                0 => ((screen_width() / 2.0 - 115.0, 5.0), "down_right"),
-                1 => ((screen_width() / 2.0 - 75.0, 5.0), "down_stright"),
-                _ => ((screen_width() / 2.0 - 35.0, 5.0), "down_left"),
-            };
-            if can_spawn(&cars, cord) {
-                cars.push(Car::new(direction.to_string(), 30, 50, cord, 180.0));
-                stats.total_cars += 1;
-            }
+            sim.request_random(&ROUTES[6..9]);
         }
-
         if is_key_pressed(KeyCode::Left) {
-            let random_case = rand::gen_range(0, 3);
-            let (cord, direction) = match random_case {
-                0 => ((screen_width() - 40.0, screen_height() / 2.0 - 125.0), "left_right"),
-                1 => ((screen_width() - 40.0, screen_height() / 2.0 - 85.0), "left_stright"),
-                _ => ((screen_width() - 40.0, screen_height() / 2.0 - 45.0), "left_left"),
-            };
-            if can_spawn(&cars, cord) {
-                cars.push(Car::new(direction.to_string(), 30, 50, cord, 270.0));
-                stats.total_cars += 1;
-            }
+            sim.request_random(&ROUTES[9..12]);
         }
-
         if is_key_pressed(KeyCode::R) {
-            let random_dir = rand::gen_range(0, 12);
-            let (cord, direction, rotation) = match random_dir {
-                0 => ((screen_width() / 2.0 + 85.0, screen_height() - 55.0), "up_right", 0.0),
-                1 => ((screen_width() / 2.0 + 45.0, screen_height() - 55.0), "up_stright", 0.0),
-                2 => ((screen_width() / 2.0 + 5.0, screen_height() - 55.0), "up_left", 0.0),
-                3 => ((10.0, screen_height() / 2.0 + 75.0), "right_right", 90.0),
-                4 => ((10.0, screen_height() / 2.0 + 35.0), "right_stright", 90.0),
-                5 => ((10.0, screen_height() / 2.0 - 5.0), "right_left", 90.0),
-                6 => ((screen_width() / 2.0 - 115.0, 5.0), "down_right", 180.0),
-                7 => ((screen_width() / 2.0 - 75.0, 5.0), "down_stright", 180.0),
-                8 => ((screen_width() / 2.0 - 35.0, 5.0), "down_left", 180.0),
-                9 => ((screen_width() - 40.0, screen_height() / 2.0 - 125.0), "left_right", 270.0),
-                10 => ((screen_width() - 40.0, screen_height() / 2.0 - 85.0), "left_stright", 270.0),
-                _ => ((screen_width() - 40.0, screen_height() / 2.0 - 45.0), "left_left", 270.0),
-            };
-
-            if can_spawn(&cars, cord) {
-                cars.push(Car::new(direction.to_string(), 30, 50, cord, rotation));
-                stats.total_cars += 1;
-            }
+            sim.request_random(&ROUTES);
+        }
+        if is_key_pressed(KeyCode::P) {
+            sim.request_random_pedestrian();
+        }
+        if is_key_pressed(KeyCode::B) {
+            sim.request_random_cyclist();
         }
-        
-        let cx = screen_width() / 2.0;
-        let cy = screen_height() / 2.0;
-
-        // Smart Intersection System: Velocity Control
-        for i in 0..cars.len() {
-            let mut requested_velocity = V_CRUISE;
-            let my_radar = cars[i].get_radar();
-            let my_rect = cars[i].get_rect();
-            let my_dist_center = ((cars[i].cord.0 - cx).powi(2) + (cars[i].cord.1 - cy).powi(2)).sqrt();
-
-            for j in 0..cars.len() {
-                if i == j { continue; }
-                let other_rect = cars[j].get_rect();
-                let other_radar = cars[j].get_radar();
-                let other_dist_center = ((cars[j].cord.0 - cx).powi(2) + (cars[j].cord.1 - cy).powi(2)).sqrt();
-                let dist_between = ((cars[i].cord.0 - cars[j].cord.0).powi(2) + (cars[i].cord.1 - cars[j].cord.1).powi(2)).sqrt();
-
-                // 1. Same Lane Safety (Rear-ending Avoidance)
-                if my_radar.intersect(other_rect).is_some() {
-                    if dist_between < SAFETY_DISTANCE {
-                        requested_velocity = V_STOP; // Hard safety stop
-                    } else if dist_between < SAFETY_DISTANCE * 1.5 {
-                        requested_velocity = requested_velocity.min(V_CRAWL); // Careful follow distance
-                    } else {
-                        requested_velocity = requested_velocity.min(V_ADJUST); // Adjusting speed 
-                    }
-                }
-
-                // 2. Intersection Right-of-Way (Cross-traffic)
-                if my_radar.intersect(other_radar).is_some() && my_rect.intersect(other_rect).is_none() {
-                    let dist_diff = my_dist_center - other_dist_center;
 
-                    if other_dist_center < 250.0 && my_dist_center >= 250.0 {
-                        // Other car is deep in intersection; we must yield
-                        requested_velocity = requested_velocity.min(V_STOP);
-                    } else if dist_diff > 40.0 {
-                        // Other car is closer to the center
-                        if my_dist_center < 200.0 {
-                            requested_velocity = requested_velocity.min(V_STOP);
-                        } else {
-                            requested_velocity = requested_velocity.min(V_CRAWL);
-                        }
-                    } else if dist_diff.abs() <= 40.0 && i > j {
-                        // Tie-breaker yield
-                        requested_velocity = requested_velocity.min(V_ADJUST);
-                    }
-                }
+        let dt = playback.sim_dt(get_frame_time(), MAX_TICK);
+        let paused = playback.paused;
+        sim.advance(dt, |sim, tick, _| {
+            history.record(sim.now(), &sim.cars, &sim.stats);
+            heatmap.record(tick, &sim.cars);
+            if let Some(server) = server.as_mut() {
+                server.tick(sim, paused);
             }
+        });
 
-            cars[i].target_velocity = requested_velocity;
+        // The selection ends when its car leaves (or the simulation is reset)
+        let selected_index = selected.and_then(|id| sim.index_of(id));
+        if selected_index.is_none() {
+            selected = None;
         }
+        view.update(selected_index.map(|i| &sim.cars[i]));
 
-        for car in cars.iter_mut() {
-            car.update(dt);
-            stats.sample_velocity(car.velocity); // Now tracking velocity correctly
+        clear_background(Color::from_rgba(4, 96, 85, 255));
+        set_camera(&view.camera());
+        draw_road(sim.config.bike.lane_width);
+        if let Some(layer) = heat_layer {
+            heatmap.draw(layer);
+        }
+        draw_cars(&car_tex, &sim.cars, selected_index);
+        draw_pedestrians(&sim.pedestrians);
+        draw_cyclists(&sim.cyclists);
+        if show_debug {
+            draw_debug(&sim.cars, &sim.commands, &sim.collisions(), &sim.config.controller);
         }
 
-        let current_time = get_time();
-        cars.retain(|car| {
-            let (x, y) = car.cord;
-            let keep = x > -30.0 && x < screen_width() + 30.0 && y > -30.0 && y < screen_height() + 30.0;
-            if !keep {
-                stats.register_passed_car((current_time - car.spawn_time) as f32);
+        set_default_camera();
+        let mut status = format!("Autonomous Vehicles Active: {}   Waiting to enter: {}", sim.cars.len(), sim.queued());
+        if let Some(server) = &server {
+            if let Ok(addr) = server.local_addr() {
+                status += &format!("   Control API: {} ({} clients)", addr, server.clients());
             }
-            keep
-        });
-
-        for car in &cars {
-            draw_texture_ex(
-                &car_tex,
-                car.cord.0,
-                car.cord.1,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(vec2(car.width as f32, car.height as f32)),
-                    rotation: car.rotation.to_radians(),
-                    ..Default::default()
-                },
-            );
         }
-
-        draw_text(&format!("Autonomous Vehicles Active: {}", cars.len()), 10.0, 24.0, 22.0, WHITE);
+        draw_text(&status, 10.0, 24.0, 22.0, WHITE);
+        draw_spawn_queues(&view, &sim);
+        if let Some((message, color, since)) = &notice {
+            if get_time() - since < NOTICE_SECONDS {
+                draw_text(message, 10.0, 48.0, 20.0, *color);
+            }
+        }
+        draw_text(&format!("{}   t = {:.1} s", playback.label(), sim.now()), screen_width() - 220.0, screen_height() - 52.0, 22.0, YELLOW);
+        draw_text(
+            "Click: select car / spawn in lane   Wheel: zoom   Right-drag: pan   Tab: select car   F: follow   Home: reset view",
+            10.0, screen_height() - 32.0, 18.0, LIGHTGRAY,
+        );
+        draw_text(
+            "Space: pause   .: step   -/+: speed   0: x1   P: pedestrian   B: cyclist   D: debug   G: charts   T: tuning   H: heatmap   X: export heatmap   Esc: statistics",
+            10.0, screen_height() - 12.0, 18.0, LIGHTGRAY,
+        );
+        if show_charts {
+            history.draw();
+        }
+        if let Some(layer) = heat_layer {
+            heatmap.draw_legend(layer);
+        }
+        if let Some(i) = selected_index {
+            draw_inspector(&sim.cars[i], &sim.commands[i], &sim.cars);
+        }
+        tuning.draw(&mut sim);
+        if show_stats {
+            draw_statistics(&sim.stats);
+        }
 
         next_frame().await;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,257 @@
+use crate::cars::Car;
+use crate::config::PedestrianConfig;
+use crate::controller::V_STOP;
+use crate::cyclists::Cyclist;
+use crate::spatial::Rect;
+use crate::world::*;
+
+// Pedestrians and the zebra crossings they use. There is one crossing per
+// arm, just outside the intersection box, in the order of the routes'
+// approaches: "up" is the arm cars come from when they drive up.
+pub const ARMS: [&str; 4] = ["up", "right", "down", "left"];
+
+pub const CROSSWALK_WIDTH: f32 = 4.0; // Along the road
+pub const CROSSWALK_OFFSET: f32 = 1.0; // From the box edge
+pub const CURB: f32 = 1.0; // Sidewalk where pedestrians wait, beyond the road edge
+pub const PEDESTRIAN_SIZE: f32 = 0.6;
+
+// Crossing of arm `arm`, across the whole road
+pub fn crosswalk(arm: usize) -> Rect {
+    let (cx, cy) = centre();
+    let near = BOX_HALF_WIDTH + CROSSWALK_OFFSET;
+    let road = 2.0 * BOX_HALF_WIDTH;
+    match arm {
+        0 => Rect::new(cx - BOX_HALF_WIDTH, cy + near, road, CROSSWALK_WIDTH),
+        1 => Rect::new(cx - near - CROSSWALK_WIDTH, cy - BOX_HALF_WIDTH, CROSSWALK_WIDTH, road),
+        2 => Rect::new(cx - BOX_HALF_WIDTH, cy - near - CROSSWALK_WIDTH, road, CROSSWALK_WIDTH),
+        _ => Rect::new(cx + near, cy - BOX_HALF_WIDTH, CROSSWALK_WIDTH, road),
+    }
+}
+
+// Unit heading of traffic coming from `approach` (in `ARMS` order), in
+// screen coordinates: y grows downwards
+pub fn heading(approach: usize) -> (i32, i32) {
+    match approach {
+        0 => (0, -1),
+        1 => (1, 0),
+        2 => (0, 1),
+        _ => (-1, 0),
+    }
+}
+
+// The arm lying in direction `towards` from the centre
+pub fn arm_towards(towards: (i32, i32)) -> usize {
+    match towards {
+        (1, 0) => 3,
+        (-1, 0) => 1,
+        (0, 1) => 0,
+        _ => 2,
+    }
+}
+
+// Arm a route leaves the intersection through
+pub fn exit_arm(route: &str) -> Option<usize> {
+    let (approach, turn) = route.split_once('_')?;
+    let (hx, hy) = heading(ARMS.iter().position(|arm| *arm == approach)?);
+    let exit = match turn {
+        "right" => (-hy, hx),
+        "left" => (hy, -hx),
+        _ => (hx, hy),
+    };
+    Some(arm_towards(exit))
+}
+
+// Crossing a turning route leaves the intersection through; nothing for
+// straight routes, which do not give way
+pub fn exit_crossing(route: &str) -> Option<usize> {
+    if route.ends_with("stright") {
+        return None;
+    }
+    exit_arm(route)
+}
+
+// Pedestrians walk along x on the vertical arms, along y on the others
+fn walks_along_x(arm: usize) -> bool {
+    matches!(arm, 0 | 2)
+}
+
+// Curb to curb, over the bike lanes on both sides of the road
+pub fn crossing_length(lane_width: f32) -> f32 {
+    2.0 * (BOX_HALF_WIDTH + lane_width + CURB)
+}
+
+#[derive(Clone, Debug)]
+pub struct Pedestrian {
+    pub arm: usize,
+    pub forward: bool,  // Walking towards increasing x (or y)
+    pub walked: f32,    // Metres from the curb where they waited
+    pub speed: f32,     // m/s
+    pub length: f32,    // Curb to curb, see `crossing_length`
+    pub requested_at: f64,
+    pub started_at: Option<f64>, // Still waiting at the curb until then
+}
+
+impl Pedestrian {
+    pub fn new(arm: usize, forward: bool, speed: f32, length: f32, requested_at: f64) -> Self {
+        Pedestrian { arm, forward, walked: 0.0, speed, length, requested_at, started_at: None }
+    }
+
+    pub fn walking(&self) -> bool {
+        self.started_at.is_some()
+    }
+
+    pub fn done(&self) -> bool {
+        self.walked >= self.length
+    }
+
+    // Coordinate along the walking axis after `walked` metres
+    fn across(&self, walked: f32) -> f32 {
+        let (cx, cy) = centre();
+        let middle = if walks_along_x(self.arm) { cx } else { cy };
+        let start = middle - self.length / 2.0;
+        if self.forward {
+            start + walked
+        } else {
+            start + self.length - walked
+        }
+    }
+
+    fn rect_at(&self, walked: f32) -> Rect {
+        let zone = crosswalk(self.arm);
+        let (zx, zy) = zone.center();
+        let half = PEDESTRIAN_SIZE / 2.0;
+        let along = self.across(walked);
+        if walks_along_x(self.arm) {
+            Rect::new(along - half, zy - half, PEDESTRIAN_SIZE, PEDESTRIAN_SIZE)
+        } else {
+            Rect::new(zx - half, along - half, PEDESTRIAN_SIZE, PEDESTRIAN_SIZE)
+        }
+    }
+
+    pub fn get_rect(&self) -> Rect {
+        self.rect_at(self.walked)
+    }
+
+    // Waits for a gap, then walks across. Turning cars give way (see
+    // `controller::yield_to_pedestrians`), so only straight traffic has to
+    // leave a gap. Never steps into a car, or right in front of a moving one:
+    // a car turning onto the crossing may only see them once it is on it.
+    // Cyclists stop for pedestrians ahead, but get the same care.
+    pub fn update(&mut self, dt: f32, now: f64, cars: &[Car], cyclists: &[Cyclist], config: &PedestrianConfig, cruise: f32) {
+        if !self.walking() {
+            if !self.gap_accepted(cars, config, cruise) {
+                return;
+            }
+            self.started_at = Some(now);
+        }
+        let next = self.walked + self.speed * dt;
+        let body = self.rect_at(next);
+        let blocked = cars.iter().any(|car| {
+            overlaps(car.get_rect(), body) || (car.velocity > V_STOP && overlaps(car.get_radar(), body))
+        }) || cyclists.iter().any(|cyclist| {
+            overlaps(cyclist.get_rect(), body) || (cyclist.velocity > 0.0 && overlaps(cyclist.get_radar(), body))
+        });
+        if !blocked {
+            self.walked = next;
+        }
+    }
+
+    // True when no car is on the crossing or turning towards it inside the
+    // box, and every straight car heading for it, driving at `cruise`, gets
+    // there `gap_margin` seconds after this pedestrian has walked past its lane
+    pub fn gap_accepted(&self, cars: &[Car], config: &PedestrianConfig, cruise: f32) -> bool {
+        let zone = crosswalk(self.arm);
+        let start = self.across(0.0);
+        cars.iter().all(|car| {
+            let body = car.get_rect();
+            if overlaps(body, zone) {
+                return false;
+            }
+            if !car.direction.ends_with("stright") {
+                return exit_crossing(&car.direction) != Some(self.arm) || !overlaps(body, intersection_box());
+            }
+            let Some(distance) = distance_ahead(body, car.current_heading(), zone) else {
+                return true;
+            };
+            let (lo, hi) = if walks_along_x(self.arm) { (body.x, body.right()) } else { (body.y, body.bottom()) };
+            let to_clear = if self.forward { hi - start } else { start - lo } + PEDESTRIAN_SIZE;
+            to_clear <= 0.0 || distance / cruise > to_clear / self.speed + config.gap_margin
+        })
+    }
+}
+
+// Overlap with some area; touching edges do not count
+pub fn overlaps(a: Rect, b: Rect) -> bool {
+    a.intersect(b).is_some_and(|overlap| overlap.w > 0.0 && overlap.h > 0.0)
+}
+
+// Distance `body` still has to drive along `heading` before it reaches
+// `zone`: 0 when already on it, nothing when it passes beside or is past it
+pub fn distance_ahead(body: Rect, heading: (f32, f32), zone: Rect) -> Option<f32> {
+    let horizontal = heading.0 != 0.0;
+    let (lo, hi, zone_lo, zone_hi, sign) = if horizontal {
+        (body.x, body.right(), zone.x, zone.right(), heading.0)
+    } else {
+        (body.y, body.bottom(), zone.y, zone.bottom(), heading.1)
+    };
+    let (side_lo, side_hi, zone_side_lo, zone_side_hi) = if horizontal {
+        (body.y, body.bottom(), zone.y, zone.bottom())
+    } else {
+        (body.x, body.right(), zone.x, zone.right())
+    };
+    if side_hi <= zone_side_lo || side_lo >= zone_side_hi {
+        return None;
+    }
+    if sign > 0.0 {
+        (lo < zone_hi).then(|| (zone_lo - hi).max(0.0))
+    } else {
+        (hi > zone_lo).then(|| (lo - zone_hi).max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crosswalks_lie_across_their_arm_outside_the_box() {
+        let zone = intersection_box();
+        for (arm, name) in ARMS.iter().enumerate() {
+            let crossing = crosswalk(arm);
+            assert!(!overlaps(crossing, zone), "{}", name);
+            // Every lane of the arm, both ways, goes through its crossing
+            let entry = crate::cars::spawn_point(&format!("{}_stright", name)).unwrap().0;
+            let lane = (entry.0 + CAR_WIDTH / 2.0, entry.1 + CAR_LENGTH / 2.0);
+            if walks_along_x(arm) {
+                assert!(crossing.x < lane.0 && lane.0 < crossing.right(), "{}", name);
+            } else {
+                assert!(crossing.y < lane.1 && lane.1 < crossing.bottom(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn turning_routes_leave_through_the_arm_they_turn_into() {
+        assert_eq!(exit_crossing("up_right"), Some(3));
+        assert_eq!(exit_crossing("up_left"), Some(1));
+        assert_eq!(exit_crossing("right_right"), Some(0));
+        assert_eq!(exit_crossing("down_left"), Some(3));
+        assert_eq!(exit_crossing("left_right"), Some(2));
+        assert_eq!(exit_crossing("left_stright"), None);
+        assert_eq!(exit_arm("left_stright"), Some(1));
+    }
+
+    #[test]
+    fn pedestrians_wait_for_straight_traffic_but_not_for_turning_cars() {
+        let config = PedestrianConfig::default();
+        let cruise = kmh(144.0);
+        let pedestrian = Pedestrian::new(0, true, config.speed, crossing_length(1.5), 0.0);
+        let car = crate::cars::approaching;
+        // Coming up the arm, at the edge of the world: 110 m take under 3 s
+        assert!(!pedestrian.gap_accepted(&[car("up_stright", 0.0)], &config, cruise));
+        assert!(pedestrian.gap_accepted(&[car("up_right", 0.0)], &config, cruise));
+        assert!(pedestrian.gap_accepted(&[], &config, cruise));
+        // Already past the crossing
+        assert!(pedestrian.gap_accepted(&[car("up_stright", 130.0)], &config, cruise));
+    }
+}
@@ -0,0 +1,71 @@
+use macroquad::prelude::*;
+
+const SCALES: [f32; 8] = [0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+const NORMAL: usize = 3;
+
+// Frames longer than this (window dragged, debugger break) are not caught up
+const MAX_FRAME: f32 = 0.1;
+
+// Pause, single-step and time-scale controls. Turns the frame time into the
+// amount of simulated time to advance this frame.
+pub struct Playback {
+    pub paused: bool,
+    scale: usize,
+    step_requested: bool,
+}
+
+impl Playback {
+    pub fn new() -> Self {
+        Playback { paused: false, scale: NORMAL, step_requested: false }
+    }
+
+    pub fn scale(&self) -> f32 {
+        SCALES[self.scale]
+    }
+
+    pub fn handle_input(&mut self) {
+        if is_key_pressed(KeyCode::Space) {
+            self.paused = !self.paused;
+        }
+        if is_key_pressed(KeyCode::Period) {
+            // Stepping implies pausing, so the step can be looked at
+            self.paused = true;
+            self.step_requested = true;
+        }
+        if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
+            self.scale = (self.scale + 1).min(SCALES.len() - 1);
+        }
+        if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
+            self.scale = self.scale.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Key0) {
+            self.scale = NORMAL;
+        }
+    }
+
+    // Simulated seconds to advance for a frame that took `frame_dt`
+    pub fn sim_dt(&mut self, frame_dt: f32, tick: f32) -> f32 {
+        if self.paused {
+            if self.step_requested {
+                self.step_requested = false;
+                return tick;
+            }
+            return 0.0;
+        }
+        frame_dt.min(MAX_FRAME) * self.scale()
+    }
+
+    pub fn label(&self) -> String {
+        if self.paused {
+            format!("PAUSED (x{})", self.scale())
+        } else {
+            format!("x{}", self.scale())
+        }
+    }
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,368 @@
+use crate::cars::ROUTES;
+use crate::config::Config;
+use crate::controller::Policy;
+use crate::env::{Action, Env, EnvConfig, Reward};
+use crate::pedestrians::ARMS;
+use crate::sim;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList};
+
+// Python bindings over the headless core, built with the `python` feature
+// (see `pyproject.toml`):
+//
+//     import traffic_sim
+//     sim = traffic_sim.Simulation({"controller": {"cruise_kmh": 100}}, seed=1)
+//     sim.spawn("up_left")
+//     sim.step(5.0)
+//     sim.cars(), sim.stats()
+//
+// Configs are dicts laid out like `traffic.toml`, or the TOML text itself.
+#[pyclass(name = "Simulation", module = "traffic_sim")]
+pub struct PySimulation {
+    sim: sim::Simulation,
+}
+
+fn name(key: &Bound<'_, PyAny>) -> PyResult<String> {
+    key.extract().map_err(|_| PyValueError::new_err("config sections and keys must be strings"))
+}
+
+fn to_config(config: Option<&Bound<'_, PyAny>>) -> PyResult<Config> {
+    let Some(config) = config else {
+        return Ok(Config::default());
+    };
+    let text = if let Ok(text) = config.extract::<String>() {
+        text
+    } else {
+        let sections = config.cast::<PyDict>().map_err(|_| PyValueError::new_err("config must be a dict or TOML text"))?;
+        let mut table = toml::Table::new();
+        for (section, values) in sections.iter() {
+            let values = values.cast::<PyDict>().map_err(|_| PyValueError::new_err("config sections must be dicts"))?;
+            let mut entries = toml::Table::new();
+            for (key, value) in values.iter() {
+                // Python bools are ints, and would pass for 0 and 1
+                let number = if value.is_instance_of::<PyBool>() { None } else { value.extract::<f64>().ok() };
+                let number = number.ok_or_else(|| PyValueError::new_err("config values must be numbers"))?;
+                entries.insert(name(&key)?, toml::Value::Float(number));
+            }
+            table.insert(name(&section)?, toml::Value::Table(entries));
+        }
+        table.to_string()
+    };
+    Config::parse(&text).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+#[pymethods]
+impl PySimulation {
+    #[new]
+    #[pyo3(signature = (config = None, seed = 0))]
+    fn py_new(config: Option<&Bound<'_, PyAny>>, seed: u64) -> PyResult<Self> {
+        let mut sim = sim::Simulation::with_seed(seed);
+        sim.set_config(to_config(config)?);
+        Ok(PySimulation { sim })
+    }
+
+    // `Simulation.new(config)`, same as calling the class
+    #[staticmethod]
+    #[pyo3(name = "new", signature = (config = None, seed = 0))]
+    fn create(config: Option<&Bound<'_, PyAny>>, seed: u64) -> PyResult<Self> {
+        Self::py_new(config, seed)
+    }
+
+    // Requests a car on `route` (one of `ROUTES`); it waits in its lane
+    // queue if the entry is blocked
+    fn spawn(&mut self, route: &str) -> PyResult<()> {
+        if !ROUTES.contains(&route) {
+            return Err(PyValueError::new_err(format!("unknown route {:?}, expected one of {:?}", route, ROUTES)));
+        }
+        self.sim.request(route);
+        Ok(())
+    }
+
+    // A pedestrian waiting at the crossing of `arm` (one of `ARMS`), to walk
+    // towards increasing x or y if `forward`
+    #[pyo3(signature = (arm, forward = true))]
+    fn spawn_pedestrian(&mut self, arm: &str, forward: bool) -> PyResult<()> {
+        let Some(index) = ARMS.iter().position(|name| *name == arm) else {
+            return Err(PyValueError::new_err(format!("unknown arm {:?}, expected one of {:?}", arm, ARMS)));
+        };
+        self.sim.request_pedestrian(index, forward);
+        Ok(())
+    }
+
+    // A cyclist riding in from `approach` (one of `ARMS`) in its bike lane
+    fn spawn_cyclist(&mut self, approach: &str) -> PyResult<()> {
+        let Some(index) = ARMS.iter().position(|name| *name == approach) else {
+            return Err(PyValueError::new_err(format!("unknown approach {:?}, expected one of {:?}", approach, ARMS)));
+        };
+        self.sim.request_cyclist(index);
+        Ok(())
+    }
+
+    // Advances `dt` simulated seconds in controller ticks; returns the number
+    // of cars that left the world meanwhile
+    fn step(&mut self, dt: f32) -> PyResult<usize> {
+        if !(dt >= 0.0 && dt.is_finite()) {
+            return Err(PyValueError::new_err("dt must be a finite number of seconds >= 0"));
+        }
+        let mut exits = 0;
+        self.sim.advance(dt, |_, _, removed| exits += removed.len());
+        Ok(exits)
+    }
+
+    fn reset(&mut self, seed: u64) {
+        self.sim.reset(seed);
+    }
+
+    fn set_config(&mut self, config: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.sim.set_config(to_config(Some(config))?);
+        Ok(())
+    }
+
+    fn config(&self) -> String {
+        self.sim.config.to_toml()
+    }
+
+    // One dict per car on the road: id, route, centre position (m), velocity
+    // and commanded velocity (m/s), rotation (degrees) and the controller's
+    // reason, ready for `pandas.DataFrame(sim.cars())`
+    fn cars<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let cars = PyList::empty(py);
+        for (car, command) in self.sim.cars.iter().zip(&self.sim.commands) {
+            let (x, y) = car.get_rect().center();
+            let entry = PyDict::new(py);
+            entry.set_item("id", car.id.0)?;
+            entry.set_item("route", &car.direction)?;
+            entry.set_item("x", x)?;
+            entry.set_item("y", y)?;
+            entry.set_item("velocity", car.velocity)?;
+            entry.set_item("target_velocity", command.velocity)?;
+            entry.set_item("rotation", car.rotation)?;
+            entry.set_item("reason", command.describe(&self.sim.cars))?;
+            entry.set_item("spawn_time", car.spawn_time)?;
+            entry.set_item("connected", car.connected())?;
+            entry.set_item("profile", car.driver.as_ref().map(|driver| driver.profile.name()))?;
+            cars.append(entry)?;
+        }
+        Ok(cars)
+    }
+
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = &self.sim.stats;
+        let dict = PyDict::new(py);
+        dict.set_item("time", self.sim.now())?;
+        dict.set_item("total_cars", stats.total_cars)?;
+        dict.set_item("passed", stats.nbr_passed)?;
+        dict.set_item("active", self.sim.cars.len())?;
+        dict.set_item("queued", self.sim.queued())?;
+        dict.set_item("average_velocity", stats.average_velocity)?;
+        dict.set_item("average_delay", stats.average_delay)?;
+        dict.set_item("total_distance", stats.total_distance)?;
+        dict.set_item("total_energy", stats.total_energy)?;
+        dict.set_item("max_queue_wait", stats.max_queue_wait)?;
+        dict.set_item("collisions", self.sim.collisions().len())?;
+        dict.set_item("pedestrians_crossed", stats.pedestrians_crossed)?;
+        dict.set_item("average_pedestrian_wait", stats.average_pedestrian_wait)?;
+        dict.set_item("max_pedestrian_wait", stats.max_pedestrian_wait)?;
+        dict.set_item("pedestrians_hit", self.sim.pedestrians_hit().len())?;
+        dict.set_item("cyclists_passed", stats.cyclists_passed)?;
+        dict.set_item("average_cyclist_delay", stats.average_cyclist_delay)?;
+        dict.set_item("max_cyclist_delay", stats.max_cyclist_delay)?;
+        dict.set_item("cyclist_near_misses", stats.cyclist_near_misses)?;
+        dict.set_item("cyclists_hit", self.sim.cyclists_hit().len())?;
+        Ok(dict)
+    }
+
+    #[getter]
+    fn time(&self) -> f64 {
+        self.sim.now()
+    }
+
+    // Random requests per minute over all routes, 0 for none
+    #[getter]
+    fn demand(&self) -> f64 {
+        self.sim.demand
+    }
+
+    #[setter]
+    fn set_demand(&mut self, demand: f64) {
+        self.sim.demand = demand.max(0.0);
+    }
+
+    // Random pedestrians per minute over all crossings, 0 for none
+    #[getter]
+    fn pedestrian_demand(&self) -> f64 {
+        self.sim.pedestrian_demand
+    }
+
+    #[setter]
+    fn set_pedestrian_demand(&mut self, demand: f64) {
+        self.sim.pedestrian_demand = demand.max(0.0);
+    }
+
+    // Random cyclists per minute over all approaches, 0 for none
+    #[getter]
+    fn cyclist_demand(&self) -> f64 {
+        self.sim.cyclist_demand
+    }
+
+    #[setter]
+    fn set_cyclist_demand(&mut self, demand: f64) {
+        self.sim.cyclist_demand = demand.max(0.0);
+    }
+
+    #[getter]
+    fn policy(&self) -> &'static str {
+        self.sim.policy.name()
+    }
+
+    #[setter]
+    fn set_policy(&mut self, name: &str) -> PyResult<()> {
+        self.sim.policy = Policy::from_name(name).ok_or_else(|| PyValueError::new_err(format!("unknown policy {:?}", name)))?;
+        Ok(())
+    }
+}
+
+// Gym-style environment (see `env.rs`). `step` takes `None` for the smart
+// controller, an int for a signal phase, or a list of target velocities per
+// observation slot, and returns `(observation, reward, done, info)`.
+#[pyclass(name = "Env", module = "traffic_sim")]
+pub struct PyEnv {
+    env: Env,
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (
+        config = None, slots = 16, ticks_per_step = 6, episode_seconds = 300.0, demand = 20.0,
+        end_on_collision = true, throughput_reward = 1.0, delay_penalty = 0.1, collision_penalty = 10.0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn py_new(
+        config: Option<&Bound<'_, PyAny>>,
+        slots: usize,
+        ticks_per_step: usize,
+        episode_seconds: f64,
+        demand: f64,
+        end_on_collision: bool,
+        throughput_reward: f32,
+        delay_penalty: f32,
+        collision_penalty: f32,
+    ) -> PyResult<Self> {
+        let config = EnvConfig {
+            sim: to_config(config)?,
+            reward: Reward { throughput: throughput_reward, delay: delay_penalty, collision: collision_penalty },
+            slots,
+            ticks_per_step: ticks_per_step.max(1),
+            episode_seconds,
+            demand,
+            end_on_collision,
+        };
+        Ok(PyEnv { env: Env::new(config) })
+    }
+
+    #[getter]
+    fn observation_len(&self) -> usize {
+        self.env.observation_len()
+    }
+
+    #[pyo3(signature = (seed = 0))]
+    fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.env.reset(seed).values
+    }
+
+    #[pyo3(signature = (action = None))]
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<(Vec<f32>, f32, bool, Bound<'py, PyDict>)> {
+        let action = match action {
+            None => Action::Controller,
+            Some(action) => match action.extract::<usize>() {
+                Ok(phase) => Action::Phase(phase),
+                Err(_) => {
+                    let velocities: Vec<f32> = action.extract().map_err(|_| {
+                        PyValueError::new_err("action must be None, a phase index or a list of velocities")
+                    })?;
+                    if velocities.iter().any(|velocity| !velocity.is_finite()) {
+                        return Err(PyValueError::new_err("velocities must be finite numbers"));
+                    }
+                    Action::Velocities(velocities)
+                }
+            },
+        };
+        let step = self.env.step(&action);
+        let info = PyDict::new(py);
+        info.set_item("time", step.info.time)?;
+        info.set_item("exits", step.info.exits)?;
+        info.set_item("collisions", step.info.collisions)?;
+        info.set_item("delay", step.info.delay)?;
+        info.set_item("deadlocked", step.info.deadlocked)?;
+        info.set_item("truncated", step.info.truncated)?;
+        Ok((step.observation.values, step.reward, step.done, info))
+    }
+}
+
+#[pymodule]
+fn traffic_sim(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySimulation>()?;
+    m.add_class::<PyEnv>()?;
+    m.add("ROUTES", ROUTES.to_vec())?;
+    m.add("ARMS", ARMS.to_vec())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The module as Python sees it, driven by a script like the notebook's
+    #[test]
+    fn python_drives_the_simulation() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "traffic_sim").unwrap();
+            traffic_sim(&module).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("traffic_sim", module).unwrap();
+            let script = c"
+sim = traffic_sim.Simulation({'controller': {'cruise_kmh': 100}}, seed=1)
+assert 'cruise_kmh = 100' in sim.config(), sim.config()
+sim.demand = 20
+sim.spawn('up_left')
+sim.spawn_pedestrian('up')
+sim.step(60.0)
+stats = sim.stats()
+assert stats['passed'] > 0 and stats['collisions'] == 0, stats
+assert all(car['route'] in traffic_sim.ROUTES for car in sim.cars())
+sim.reset(2)
+assert sim.time == 0 and sim.demand == 20 and sim.stats()['total_cars'] == 0
+
+for bad in ({'controller': {'cruise_kmh': True}}, {'controller': {'cruise_kmh': 'fast'}}):
+    try:
+        traffic_sim.Simulation(bad)
+    except ValueError as err:
+        assert str(err) == 'config values must be numbers', err
+    else:
+        raise AssertionError('accepted %r' % bad)
+
+env = traffic_sim.Env(demand=20)
+observation = env.reset(3)
+assert len(observation) == env.observation_len
+observation, reward, done, info = env.step(None)
+assert not done and info['collisions'] == 0
+try:
+    env.step([float('nan')])
+except ValueError as err:
+    assert str(err) == 'velocities must be finite numbers', err
+else:
+    raise AssertionError('accepted a NaN velocity')
+";
+            if let Err(err) = py.run(script, Some(&globals), None) {
+                panic!("{}", err);
+            }
+        });
+    }
+}
@@ -0,0 +1,836 @@
+use crate::cars::*;
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::controller::{Command, Policy, Reason, V_STOP};
+use crate::events::{EventKind, Events};
+use crate::cyclists::{Cyclist, CYCLIST_LENGTH, LOOKAHEAD};
+use crate::drivers::{self, Driver};
+use crate::pedestrians::{crossing_length, overlaps, Pedestrian, ARMS};
+use crate::rng::Rng;
+use crate::spatial::{Grid, Rect};
+use crate::stats::Stats;
+use crate::world::*;
+use std::collections::VecDeque;
+
+// Longest time advanced in one controller tick. Larger steps (fast time
+// scales, slow frames) are split so that cars never move further than their
+// radar can see between two decisions.
+pub const MAX_TICK: f32 = 1.0 / 60.0;
+
+// The simulated intersection: the cars, the controller's latest commands
+// (index-aligned with `cars`) and the statistics, advanced by simulated time
+// only: every timestamp comes from `clock`, never from the wall clock.
+//
+// Cars requested while their lane entry is blocked wait in a virtual queue
+// per route (`queues`, in `ROUTES` order, holding request times) and enter as
+// soon as the gap check passes. Their trip time counts from the request.
+//
+// Every car gets the next `VehicleId` when it enters; `cars` stays sorted by
+// id. What happens to the cars is reported through `events`. Pedestrians
+// appear at a curb, wait for a gap and walk across a crossing. Cyclists ride
+// straight through in the bike lanes; one requested while the start of its
+// lane is taken waits in `cyclist_queues` (per approach, in `ARMS` order).
+#[derive(Clone)]
+pub struct Simulation {
+    pub cars: Vec<Car>,
+    pub commands: Vec<Command>,
+    pub stats: Stats,
+    pub clock: Clock,
+    pub queues: [VecDeque<f64>; 12],
+    pub policy: Policy,
+    pub config: Config,
+    pub demand: f64, // Random requests per minute over all routes; 0 for none
+    pub pedestrians: Vec<Pedestrian>,
+    pub pedestrian_demand: f64, // Random pedestrians per minute over all crossings
+    pub cyclists: Vec<Cyclist>,
+    pub cyclist_demand: f64, // Random cyclists per minute over all approaches
+    pub cyclist_queues: [VecDeque<f64>; 4],
+    pub events: Events,
+    rng: Rng,
+    next_id: u64,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    // Two simulations with the same seed and the same calls behave identically
+    pub fn with_seed(seed: u64) -> Self {
+        Simulation {
+            cars: Vec::new(),
+            commands: Vec::new(),
+            stats: Stats::new(),
+            clock: Clock::new(),
+            queues: Default::default(),
+            policy: Policy::Smart,
+            config: Config::new(),
+            demand: 0.0,
+            pedestrians: Vec::new(),
+            pedestrian_demand: 0.0,
+            cyclists: Vec::new(),
+            cyclist_demand: 0.0,
+            cyclist_queues: Default::default(),
+            events: Events::new(),
+            rng: Rng::new(seed),
+            next_id: 0,
+        }
+    }
+
+    // Starts over with `seed`: the cars, pedestrians, cyclists, queues, clock
+    // and statistics go; the config, the policy, the demands and the event
+    // subscribers stay. The cars on the road are reported as despawned.
+    pub fn reset(&mut self, seed: u64) {
+        self.clear();
+        let mut fresh = Simulation::with_seed(seed);
+        fresh.events = std::mem::take(&mut self.events);
+        fresh.set_config(self.config);
+        fresh.policy = self.policy;
+        fresh.demand = self.demand;
+        fresh.pedestrian_demand = self.pedestrian_demand;
+        fresh.cyclist_demand = self.cyclist_demand;
+        *self = fresh;
+    }
+
+    pub fn clear(&mut self) {
+        let now = self.now();
+        for car in &self.cars {
+            self.events.emit(now, car.id, EventKind::Despawned);
+        }
+        self.cars.clear();
+        self.commands.clear();
+        self.pedestrians.clear();
+        self.cyclists.clear();
+        for queue in self.queues.iter_mut() {
+            queue.clear();
+        }
+        for queue in self.cyclist_queues.iter_mut() {
+            queue.clear();
+        }
+    }
+
+    // Takes effect from the next tick. Cars already on the road get the new
+    // physics but keep their size; see `Car::configure`. Pedestrians and
+    // cyclists get the new speed, but keep the bike lane width they started
+    // with.
+    pub fn set_config(&mut self, config: Config) {
+        self.config = config;
+        for car in self.cars.iter_mut() {
+            car.configure(&config.car);
+        }
+        for pedestrian in self.pedestrians.iter_mut() {
+            pedestrian.speed = config.pedestrian.speed;
+        }
+        for cyclist in self.cyclists.iter_mut() {
+            cyclist.speed = config.bike.speed();
+        }
+    }
+
+    pub fn now(&self) -> f64 {
+        self.clock.now()
+    }
+
+    // Where the car is in `cars` this tick, if it is on the road
+    pub fn index_of(&self, id: VehicleId) -> Option<usize> {
+        self.cars.binary_search_by_key(&id, |car| car.id).ok()
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queues.iter().map(|queue| queue.len()).sum()
+    }
+
+    // Pairs of cars whose bodies overlap; touching edges do not count
+    pub fn collisions(&self) -> Vec<(usize, usize)> {
+        let rects: Vec<Rect> = self.cars.iter().map(|car| car.get_rect()).collect();
+        let longest = self.cars.iter().map(|car| car.width.max(car.height)).fold(0.0, f32::max);
+        let grid = Grid::new(&rects, longest.max(1.0) * 2.0);
+        let mut pairs = Vec::new();
+        let mut candidates = Vec::new();
+        for i in 0..rects.len() {
+            grid.query(rects[i], &mut candidates);
+            for &j in candidates.iter().filter(|&&j| j > i) {
+                if let Some(overlap) = rects[i].intersect(rects[j]) {
+                    if overlap.w > 0.0 && overlap.h > 0.0 {
+                        pairs.push((i, j));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+
+    fn can_spawn(&self, spawn_cord: (f32, f32)) -> bool {
+        let safe_dist = self.config.spawn.gap;
+        for car in &self.cars {
+            let dist = ((car.cord.0 - spawn_cord.0).powi(2) + (car.cord.1 - spawn_cord.1).powi(2)).sqrt();
+            if dist < safe_dist {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn place(&mut self, direction: &str, cord: (f32, f32), rotation: f32, requested_at: f64) {
+        let mut car = Car::new(direction.to_string(), cord, rotation, requested_at, &self.config);
+        car.id = VehicleId(self.next_id);
+        self.next_id += 1;
+        // No draw at full penetration, so all-AV runs replay as before
+        let human = &self.config.human;
+        if human.penetration < 1.0 && self.rng.next_f64() >= human.penetration as f64 {
+            let driver = Driver::random(human, &mut self.rng);
+            car.velocity = driver.speed;
+            car.driver = Some(driver);
+        }
+        car.target_velocity = car.velocity;
+        // Until the next tick plans for it, the new car simply keeps cruising
+        self.commands.push(Command { velocity: car.target_velocity, reason: Reason::Clear });
+        self.events.emit(self.now(), car.id, EventKind::Spawned);
+        self.cars.push(car);
+    }
+
+    // Places a car at an exact position, e.g. a clicked point along a lane.
+    // Nothing is queued if the spot is taken.
+    pub fn spawn(&mut self, direction: &str, cord: (f32, f32), rotation: f32) -> bool {
+        if !self.can_spawn(cord) {
+            return false;
+        }
+        self.place(direction, cord, rotation, self.now());
+        self.stats.total_cars += 1;
+        true
+    }
+
+    // Asks for a car on `direction` at its lane entry. It enters now if the
+    // entry is clear and no earlier car is waiting, otherwise it is queued.
+    pub fn request(&mut self, direction: &str) {
+        let Some(lane) = ROUTES.iter().position(|route| *route == direction) else {
+            return;
+        };
+        self.stats.total_cars += 1;
+        self.queues[lane].push_back(self.now());
+        self.release_queues();
+    }
+
+    pub fn request_random(&mut self, routes: &[&str]) {
+        let direction = routes[self.rng.range(0, routes.len())];
+        self.request(direction);
+    }
+
+    // Lets the head of every lane queue in while its entry has a safe gap
+    fn release_queues(&mut self) {
+        for (lane, direction) in ROUTES.iter().enumerate() {
+            let Some((cord, rotation)) = spawn_point(direction) else {
+                continue;
+            };
+            while let Some(&requested_at) = self.queues[lane].front() {
+                if !self.can_spawn(cord) {
+                    break;
+                }
+                self.queues[lane].pop_front();
+                let wait = self.clock.since(requested_at);
+                if wait > 0.0 {
+                    self.stats.register_queue_wait(wait);
+                }
+                self.place(direction, cord, rotation, requested_at);
+            }
+        }
+    }
+
+    // A pedestrian at the curb of crossing `arm` (in `ARMS` order), to walk
+    // towards increasing x or y if `forward`
+    pub fn request_pedestrian(&mut self, arm: usize, forward: bool) {
+        if arm >= ARMS.len() {
+            return;
+        }
+        let speed = self.config.pedestrian.speed;
+        let length = crossing_length(self.config.bike.lane_width);
+        self.pedestrians.push(Pedestrian::new(arm, forward, speed, length, self.now()));
+    }
+
+    pub fn request_random_pedestrian(&mut self) {
+        let arm = self.rng.range(0, ARMS.len());
+        let forward = self.rng.range(0, 2) == 0;
+        self.request_pedestrian(arm, forward);
+    }
+
+    // A cyclist riding in from `approach` (in `ARMS` order), through the
+    // bike lane on that side; queued while the lane start is taken
+    pub fn request_cyclist(&mut self, approach: usize) {
+        if approach >= ARMS.len() {
+            return;
+        }
+        self.cyclist_queues[approach].push_back(self.now());
+        self.release_cyclists();
+    }
+
+    pub fn request_random_cyclist(&mut self) {
+        let approach = self.rng.range(0, ARMS.len());
+        self.request_cyclist(approach);
+    }
+
+    fn release_cyclists(&mut self) {
+        for approach in 0..ARMS.len() {
+            let free = self
+                .cyclists
+                .iter()
+                .all(|cyclist| cyclist.approach != approach || cyclist.travelled >= CYCLIST_LENGTH + LOOKAHEAD);
+            if !free {
+                continue;
+            }
+            if let Some(requested_at) = self.cyclist_queues[approach].pop_front() {
+                self.cyclists.push(Cyclist::new(approach, &self.config.bike, requested_at));
+            }
+        }
+    }
+
+    // Pairs of a car and a cyclist it overlaps, as (car, cyclist) indices
+    pub fn cyclists_hit(&self) -> Vec<(usize, usize)> {
+        cars_hitting(&self.cars, self.cyclists.iter().map(Cyclist::get_rect))
+    }
+
+    // Pairs of a car and a pedestrian it overlaps, as (car, pedestrian) indices
+    pub fn pedestrians_hit(&self) -> Vec<(usize, usize)> {
+        cars_hitting(&self.cars, self.pedestrians.iter().map(Pedestrian::get_rect))
+    }
+
+    // Sets the target velocity of car `index` until it is changed again;
+    // only lasting under `Policy::External`, other policies replan each tick.
+    // Returns false, changing nothing, for a car that is not there or is
+    // human-driven: those only follow what their driver sees.
+    pub fn command(&mut self, index: usize, velocity: f32) -> bool {
+        if self.cars.get(index).is_some_and(|car| car.driver.is_some()) {
+            return false;
+        }
+        match self.commands.get_mut(index) {
+            Some(command) => {
+                *command = Command { velocity: velocity.max(V_STOP), reason: Reason::External };
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Advances the simulation by `dt` seconds, in ticks of at most
+    // `MAX_TICK`. `on_tick` runs after every tick with its length and the
+    // indices (before removal) of the cars that left the world during it.
+    pub fn advance(&mut self, dt: f32, mut on_tick: impl FnMut(&Simulation, f32, &[usize])) {
+        if dt <= 0.0 {
+            return;
+        }
+        let ticks = (dt / MAX_TICK).ceil().max(1.0) as usize;
+        let tick = dt / ticks as f32;
+        for _ in 0..ticks {
+            let removed = self.tick(tick);
+            on_tick(self, tick, &removed);
+        }
+    }
+
+    // One controller tick: drop the cars that left the world, plan, move.
+    // Returns the indices of the removed cars.
+    pub fn tick(&mut self, dt: f32) -> Vec<usize> {
+        self.events.start_tick();
+        let mut removed = Vec::new();
+        let mut index = 0;
+        let now = self.now();
+        let clock = self.clock;
+        let stats = &mut self.stats;
+        let events = &mut self.events;
+        let free_flow = self.config.controller.cruise();
+        self.cars.retain(|car| {
+            let (x, y) = car.cord;
+            let keep = x > -3.0 && x < WORLD_WIDTH + 3.0 && y > -3.0 && y < WORLD_HEIGHT + 3.0;
+            if !keep {
+                stats.register_passed_car(clock.since(car.spawn_time), car.energy, car.travelled, free_flow);
+                events.emit(now, car.id, EventKind::Despawned);
+                removed.push(index);
+            }
+            index += 1;
+            keep
+        });
+
+        self.pedestrians.retain(|pedestrian| {
+            if pedestrian.done() {
+                let started = pedestrian.started_at.unwrap_or(now);
+                stats.register_crossing((started - pedestrian.requested_at) as f32);
+            }
+            !pedestrian.done()
+        });
+        self.cyclists.retain(|cyclist| {
+            if cyclist.done() {
+                let riding = cyclist.run_length() / cyclist.speed;
+                stats.register_cyclist((clock.since(cyclist.spawn_time) - riding).max(0.0));
+            }
+            !cyclist.done()
+        });
+
+        match self.policy.plan(&self.cars, &self.pedestrians, &self.cyclists, &self.config.controller) {
+            Some(commands) => self.commands = commands,
+            None => {
+                for &index in removed.iter().rev() {
+                    self.commands.remove(index);
+                }
+            }
+        }
+        // People drive by what they see, whatever they are commanded
+        if self.cars.iter().any(|car| !car.connected()) {
+            let seen = drivers::perceive(&self.cars, &self.pedestrians, &self.cyclists);
+            let ids: Vec<VehicleId> = self.cars.iter().map(|car| car.id).collect();
+            for ((car, command), seen) in self.cars.iter_mut().zip(self.commands.iter_mut()).zip(seen) {
+                if let Some(driver) = car.driver.as_mut() {
+                    *command = driver.react(now, seen, &ids);
+                }
+            }
+        }
+        for (car, command) in self.cars.iter_mut().zip(&self.commands) {
+            car.target_velocity = command.velocity;
+        }
+
+        for car in self.cars.iter_mut() {
+            car.update(dt);
+            self.stats.sample_velocity(car.velocity, dt);
+        }
+        let cruise = self.config.controller.cruise();
+        for pedestrian in self.pedestrians.iter_mut() {
+            pedestrian.update(dt, now, &self.cars, &self.cyclists, &self.config.pedestrian, cruise);
+        }
+        let bodies: Vec<Rect> = self.cyclists.iter().map(|cyclist| cyclist.get_rect()).collect();
+        for cyclist in self.cyclists.iter_mut() {
+            cyclist.update(dt, &self.cars, &self.pedestrians, &bodies, &self.config.bike, cruise);
+        }
+        self.count_near_misses();
+        self.clock.advance(dt);
+        self.report_changes();
+        // At most one arrival per tick, which is plenty below 3600 per minute
+        if self.demand > 0.0 && self.rng.next_f64() < self.demand / 60.0 * dt as f64 {
+            self.request_random(&ROUTES);
+        }
+        if self.pedestrian_demand > 0.0 && self.rng.next_f64() < self.pedestrian_demand / 60.0 * dt as f64 {
+            self.request_random_pedestrian();
+        }
+        if self.cyclist_demand > 0.0 && self.rng.next_f64() < self.cyclist_demand / 60.0 * dt as f64 {
+            self.request_random_cyclist();
+        }
+        self.release_queues();
+        self.release_cyclists();
+        self.events.end_tick();
+
+        removed
+    }
+
+    // Counts each car whose radar newly reaches a cyclist as one near miss
+    fn count_near_misses(&mut self) {
+        for cyclist in self.cyclists.iter_mut() {
+            let body = cyclist.get_rect();
+            let near: Vec<VehicleId> =
+                self.cars.iter().filter(|car| overlaps(car.get_radar(), body)).map(|car| car.id).collect();
+            for id in &near {
+                if !cyclist.near.contains(id) {
+                    self.stats.register_cyclist_near_miss();
+                }
+            }
+            cyclist.near = near;
+        }
+    }
+
+    // Emits the events for what changed about each car during the tick
+    fn report_changes(&mut self) {
+        let now = self.now();
+        let zone = intersection_box();
+        let ids: Vec<VehicleId> = self.cars.iter().map(|car| car.id).collect();
+        for (car, command) in self.cars.iter_mut().zip(&self.commands) {
+            let in_box = car.get_rect().intersect(zone).is_some_and(|overlap| overlap.w > 0.0 && overlap.h > 0.0);
+            if in_box != car.in_box {
+                car.in_box = in_box;
+                let kind = if in_box { EventKind::EnteredBox } else { EventKind::ExitedBox };
+                self.events.emit(now, car.id, kind);
+            }
+            let stopped = car.velocity <= V_STOP;
+            if stopped != car.stopped {
+                car.stopped = stopped;
+                let kind = if stopped { EventKind::Stopped } else { EventKind::Resumed };
+                self.events.emit(now, car.id, kind);
+            }
+            let yielding_to = command.yielding_to().map(|j| ids[j]);
+            if yielding_to != car.yielding_to {
+                car.yielding_to = yielding_to;
+                if let Some(to) = yielding_to {
+                    self.events.emit(now, car.id, EventKind::Yielded { to });
+                }
+            }
+        }
+    }
+}
+
+// Pairs of a car and a body (pedestrian, cyclist) it overlaps, as (car, body)
+// indices
+fn cars_hitting(cars: &[Car], bodies: impl Iterator<Item = Rect>) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (k, body) in bodies.enumerate() {
+        for (i, car) in cars.iter().enumerate() {
+            if overlaps(car.get_rect(), body) {
+                pairs.push((i, k));
+            }
+        }
+    }
+    pairs
+}
+
+// Where car `index` moved once the cars in `removed` (ascending indices from
+// the same tick) were taken out; `None` if it was removed itself
+pub fn shift_index(index: usize, removed: &[usize]) -> Option<usize> {
+    if removed.contains(&index) {
+        return None;
+    }
+    Some(index - removed.iter().filter(|&&r| r < index).count())
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const TICKS_PER_SECOND: usize = 60;
+
+    // Runs `seconds` of simulated time, failing on the first overlap
+    fn run_without_collisions(sim: &mut Simulation, seconds: usize) -> Result<(), TestCaseError> {
+        for _ in 0..seconds * TICKS_PER_SECOND {
+            sim.tick(MAX_TICK);
+            let collisions = sim.collisions();
+            prop_assert!(
+                collisions.is_empty(),
+                "collision at t = {:.2} s between {} and {}",
+                sim.now(),
+                sim.cars[collisions[0].0].direction,
+                sim.cars[collisions[0].1].direction,
+            );
+        }
+        Ok(())
+    }
+
+    // Five minutes of traffic at the demands set on `sim` in which nobody is
+    // hit: no car touches another car, a pedestrian or a cyclist. Returns
+    // whether some car gave way for a reason matching `gave_way`.
+    fn soak(sim: &mut Simulation, gave_way: impl Fn(Reason) -> bool) -> bool {
+        let mut seen = false;
+        for _ in 0..300 * TICKS_PER_SECOND {
+            sim.tick(MAX_TICK);
+            assert!(sim.collisions().is_empty(), "cars touched at t = {:.2} s", sim.now());
+            assert!(sim.pedestrians_hit().is_empty(), "pedestrian hit at t = {:.2} s", sim.now());
+            assert!(sim.cyclists_hit().is_empty(), "cyclist hit at t = {:.2} s", sim.now());
+            seen |= sim.commands.iter().any(|command| gave_way(command.reason));
+        }
+        seen
+    }
+
+    #[test]
+    fn blocked_request_waits_in_its_lane_queue() {
+        let mut sim = Simulation::new();
+        sim.request("up_stright");
+        sim.request("up_stright");
+        assert_eq!(sim.cars.len(), 1);
+        assert_eq!(sim.queued(), 1);
+        assert_eq!(sim.stats.total_cars, 2);
+
+        for _ in 0..TICKS_PER_SECOND {
+            sim.tick(MAX_TICK);
+        }
+        assert_eq!(sim.cars.len(), 2);
+        assert_eq!(sim.queued(), 0);
+        assert!(sim.stats.max_queue_wait > 0.0);
+    }
+
+    #[test]
+    fn queued_car_enters_later_stamped_with_its_request_time() {
+        let mut sim = Simulation::new();
+        sim.advance(2.0, |_, _, _| {});
+        sim.request("left_stright");
+        let requested_at = sim.now();
+        sim.request("left_stright");
+        assert_eq!(sim.cars.len(), 1);
+        while sim.cars.len() < 2 {
+            sim.tick(MAX_TICK);
+            assert!(sim.now() < 10.0, "still queued");
+        }
+        assert!(sim.now() > requested_at);
+        assert_eq!(sim.cars[1].spawn_time, requested_at);
+        assert_eq!(sim.stats.queued_cars, 1);
+        assert!((sim.stats.max_queue_wait as f64 - (sim.now() - requested_at)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reset_starts_over_with_the_same_settings() {
+        let mut sim = Simulation::with_seed(5);
+        sim.config.controller.cruise_kmh = 40.0;
+        sim.policy = Policy::Uncontrolled;
+        sim.demand = 30.0;
+        sim.pedestrian_demand = 4.0;
+        sim.advance(20.0, |_, _, _| {});
+        assert!(!sim.cars.is_empty());
+
+        sim.reset(9);
+        assert!(sim.cars.is_empty() && sim.pedestrians.is_empty() && sim.queued() == 0);
+        assert_eq!((sim.now(), sim.stats.total_cars), (0.0, 0));
+        assert_eq!(sim.config.controller.cruise_kmh, 40.0);
+        assert_eq!((sim.policy, sim.demand, sim.pedestrian_demand), (Policy::Uncontrolled, 30.0, 4.0));
+
+        // and replays like a simulation created with that seed
+        let mut fresh = Simulation::with_seed(9);
+        fresh.set_config(sim.config);
+        fresh.policy = sim.policy;
+        (fresh.demand, fresh.pedestrian_demand) = (30.0, 4.0);
+        sim.advance(20.0, |_, _, _| {});
+        fresh.advance(20.0, |_, _, _| {});
+        let cords = |sim: &Simulation| sim.cars.iter().map(|car| car.cord).collect::<Vec<_>>();
+        assert_eq!(cords(&sim), cords(&fresh));
+    }
+
+    #[test]
+    fn time_lived_follows_the_simulation_clock() {
+        let mut sim = Simulation::new();
+        sim.request("right_stright");
+        while sim.stats.nbr_passed == 0 {
+            sim.tick(MAX_TICK);
+        }
+        // Straight across the world at cruise velocity, plus the acceleration
+        // from a standstill being skipped: cars enter at cruise velocity
+        let expected = (WORLD_WIDTH + 2.0) / sim.config.controller.cruise();
+        assert!((sim.stats.max_time - expected).abs() < 0.1, "{} vs {}", sim.stats.max_time, expected);
+    }
+
+    #[test]
+    fn config_changes_apply_to_new_and_existing_cars() {
+        let mut sim = Simulation::new();
+        sim.request("right_stright");
+        let mut config = Config::new();
+        config.controller.cruise_kmh = 72.0;
+        config.car.radar_length = 4.0;
+        config.car.length = 6.0;
+        sim.set_config(config);
+        sim.request("left_stright");
+
+        assert_eq!(sim.cars[0].radar_length, 4.0);
+        assert_eq!(sim.cars[0].height, CAR_LENGTH);
+        assert_eq!(sim.cars[1].height, 6.0);
+        assert_eq!(sim.cars[1].velocity, kmh(72.0));
+        sim.tick(MAX_TICK);
+        assert!(sim.commands.iter().all(|command| command.velocity == kmh(72.0)));
+    }
+
+    #[test]
+    fn demand_requests_cars_at_the_given_rate() {
+        let mut sim = Simulation::with_seed(3);
+        sim.demand = 30.0;
+        sim.advance(600.0, |_, _, _| {});
+        // 300 expected; a Poisson count is within 3.5 standard deviations
+        let requested = sim.stats.total_cars as f64;
+        assert!((requested - 300.0).abs() < 3.5 * 300f64.sqrt(), "{} requests", requested);
+    }
+
+    #[test]
+    fn advance_splits_large_steps_into_ticks() {
+        let mut ticks = 0;
+        let mut sim = Simulation::new();
+        sim.advance(1.0, |_, tick, _| {
+            assert!(tick <= MAX_TICK);
+            ticks += 1;
+        });
+        assert_eq!(ticks, 60);
+        assert!((sim.now() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ids_stay_with_their_cars_and_are_never_reused() {
+        let mut sim = Simulation::new();
+        sim.request("right_stright");
+        for _ in 0..TICKS_PER_SECOND {
+            sim.tick(MAX_TICK);
+        }
+        sim.request("up_stright");
+        let later = sim.cars[1].id;
+        while sim.cars.len() == 2 {
+            sim.tick(MAX_TICK);
+        }
+        // The first car left: the second moved to index 0 but kept its id
+        assert_eq!(sim.index_of(later), Some(0));
+        assert_eq!(sim.index_of(VehicleId(0)), None);
+        sim.clear();
+        sim.request("down_left");
+        assert_eq!(sim.cars[0].id, VehicleId(2));
+    }
+
+    #[test]
+    fn events_follow_a_car_through_the_box() {
+        let mut sim = Simulation::new();
+        let events = sim.events.subscribe();
+        sim.request("right_stright");
+        // Reported with the first tick, although it happened before it
+        sim.tick(MAX_TICK);
+        assert_eq!(sim.events.recent()[0].kind, EventKind::Spawned);
+        while sim.stats.nbr_passed == 0 {
+            sim.tick(MAX_TICK);
+        }
+        let kinds: Vec<EventKind> = events.try_iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, [EventKind::Spawned, EventKind::EnteredBox, EventKind::ExitedBox, EventKind::Despawned]);
+        assert_eq!(sim.events.recent().len(), 1);
+    }
+
+    #[test]
+    fn subscribers_keep_receiving_events_after_a_reset() {
+        let mut sim = Simulation::new();
+        let events = sim.events.subscribe();
+        sim.request("up_left");
+        sim.reset(4);
+        sim.request("down_left");
+        let kinds: Vec<EventKind> = events.try_iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, [EventKind::Spawned, EventKind::Despawned, EventKind::Spawned]);
+    }
+
+    #[test]
+    fn events_report_yielding_and_stops() {
+        let mut sim = Simulation::new();
+        let events = sim.events.subscribe();
+        sim.request("right_stright");
+        for _ in 0..40 {
+            sim.tick(MAX_TICK);
+        }
+        // Arrives second, across the first car's path
+        sim.request("up_stright");
+        while !sim.cars.is_empty() {
+            sim.tick(MAX_TICK);
+        }
+        let kinds: Vec<EventKind> =
+            events.try_iter().filter(|event| event.vehicle == VehicleId(1)).map(|event| event.kind).collect();
+        let at = |kind| kinds.iter().position(|k| *k == kind).unwrap_or_else(|| panic!("no {:?} in {:?}", kind, kinds));
+        assert!(at(EventKind::Yielded { to: VehicleId(0) }) < at(EventKind::Stopped));
+        assert!(at(EventKind::Stopped) < at(EventKind::Resumed));
+        assert_eq!(kinds.last(), Some(&EventKind::Despawned));
+    }
+
+    #[test]
+    fn pedestrian_crosses_an_empty_road_without_waiting() {
+        let mut sim = Simulation::new();
+        sim.request_pedestrian(1, false);
+        let crossing = crossing_length(sim.config.bike.lane_width) / sim.config.pedestrian.speed;
+        while sim.stats.pedestrians_crossed == 0 {
+            sim.tick(MAX_TICK);
+            assert!(sim.now() < crossing as f64 + 0.1, "still walking at t = {:.2} s", sim.now());
+        }
+        assert!(sim.pedestrians.is_empty());
+        assert_eq!(sim.stats.max_pedestrian_wait, 0.0);
+    }
+
+    #[test]
+    fn turning_cars_give_way_and_nobody_is_hit() {
+        let mut sim = Simulation::with_seed(3);
+        sim.demand = 10.0;
+        sim.pedestrian_demand = 6.0;
+        assert!(soak(&mut sim, |reason| matches!(reason, Reason::Pedestrian(_))));
+        assert!(sim.stats.pedestrians_crossed >= 15, "{} crossed", sim.stats.pedestrians_crossed);
+        assert!(sim.stats.max_pedestrian_wait > 0.0);
+        assert!(sim.stats.nbr_passed >= 30, "{} cars passed", sim.stats.nbr_passed);
+    }
+
+    #[test]
+    fn right_turning_cars_wait_for_cyclists_riding_up_beside_them() {
+        let mut sim = Simulation::new();
+        sim.request_cyclist(0);
+        sim.advance(22.0, |_, _, _| {});
+        sim.request("up_right");
+        let mut waited = false;
+        while sim.stats.nbr_passed == 0 {
+            sim.tick(MAX_TICK);
+            assert!(sim.cyclists_hit().is_empty(), "cyclist hit at t = {:.2} s", sim.now());
+            waited |= sim.commands.first().is_some_and(|command| command.reason == Reason::Cyclist(0));
+            assert!(sim.now() < 60.0);
+        }
+        assert!(waited);
+        assert_eq!(sim.stats.cyclist_near_misses, 0);
+    }
+
+    #[test]
+    fn cyclists_get_through_mixed_traffic_unhurt() {
+        let mut sim = Simulation::with_seed(3);
+        sim.demand = 10.0;
+        sim.pedestrian_demand = 4.0;
+        sim.cyclist_demand = 6.0;
+        assert!(soak(&mut sim, |reason| matches!(reason, Reason::Cyclist(_))));
+        assert!(sim.stats.cyclists_passed >= 15, "{} cyclists passed", sim.stats.cyclists_passed);
+        assert!(sim.stats.max_cyclist_delay > 0.0);
+        assert!(sim.stats.nbr_passed >= 30, "{} cars passed", sim.stats.nbr_passed);
+    }
+
+    #[test]
+    fn human_drivers_ignore_commands_and_react_late() {
+        let mut sim = Simulation::new();
+        sim.config.human.penetration = 0.0;
+        sim.policy = Policy::External;
+        sim.request("up_stright");
+        sim.request("left_stright");
+        let driver = sim.cars[0].driver.clone().expect("every car is human-driven");
+        assert!(sim.cars[1].driver.is_some());
+        assert_eq!(sim.cars[0].velocity, driver.speed);
+        assert!(!sim.command(0, 0.0));
+        sim.advance(1.0, |_, _, _| {});
+        assert_eq!(sim.cars[0].velocity, driver.speed);
+
+        // Seeing a car stopped ahead, they only brake a reaction time later
+        let (x, y) = sim.cars[0].cord;
+        let blocker = (x, y - CAR_LENGTH - 25.0);
+        sim.config.human.penetration = 1.0;
+        assert!(sim.spawn("up_stright", blocker, 0.0));
+        sim.command(2, 0.0);
+        let seen = sim.now();
+        while sim.cars[0].target_velocity > V_STOP {
+            sim.tick(MAX_TICK);
+        }
+        assert!(sim.now() - seen >= driver.reaction_time as f64 - 1e-3, "braked after {:.2} s", sim.now() - seen);
+    }
+
+    #[test]
+    fn mixed_traffic_keeps_flowing() {
+        // Same seed and demand, all AVs against half of the cars human-driven
+        let run = |penetration| {
+            let mut sim = Simulation::with_seed(2);
+            sim.config.human.penetration = penetration;
+            sim.demand = 10.0;
+            assert!(soak(&mut sim, |reason| matches!(reason, Reason::Yielding(_))));
+            sim.stats
+        };
+        let (avs, mixed) = (run(1.0), run(0.5));
+        // Nearly as many cars get through, a few seconds later each
+        assert!(mixed.nbr_passed * 10 >= avs.nbr_passed * 9, "{} of {} cars passed", mixed.nbr_passed, avs.nbr_passed);
+        assert!(mixed.average_delay > avs.average_delay + 1.0, "humans drive slower than the AVs cruise");
+        assert!(mixed.average_delay < 15.0, "delay {:.1} s", mixed.average_delay);
+    }
+
+    // Bursts included: gaps from none to 3 s, about 40 requests a minute on
+    // average, as dense as the heaviest batch runs
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn random_spawns_never_overlap_and_always_clear(
+            requests in prop::collection::vec((0..ROUTES.len(), 0usize..180), 1..40),
+        ) {
+            let mut sim = Simulation::new();
+            for (route, gap) in requests {
+                sim.request(ROUTES[route]);
+                for _ in 0..gap {
+                    sim.tick(MAX_TICK);
+                    prop_assert!(sim.collisions().is_empty(), "collision at t = {:.2} s", sim.now());
+                }
+            }
+
+            // No deadlock: everything drains within 30 s of the last request
+            run_without_collisions(&mut sim, 30)?;
+            prop_assert!(sim.cars.is_empty() && sim.queued() == 0,
+                "{} cars still on the road, {} queued", sim.cars.len(), sim.queued());
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+// Axis-aligned rectangle in world metres, y down. Edges count as inside for
+// `intersect`, so rects that only touch intersect with zero width or height.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+impl Rect {
+    pub const fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+        Rect { x, y, w, h }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.w
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.h
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        (self.x + self.w * 0.5, self.y + self.h * 0.5)
+    }
+
+    // Left and top edges are inside, right and bottom edges are not
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        point.0 >= self.x && point.0 < self.right() && point.1 >= self.y && point.1 < self.bottom()
+    }
+
+    // Smallest rect containing both
+    pub fn combine_with(self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect { x, y, w: self.right().max(other.right()) - x, h: self.bottom().max(other.bottom()) - y }
+    }
+
+    pub fn intersect(&self, other: Rect) -> Option<Rect> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right < left || bottom < top {
+            return None;
+        }
+        Some(Rect { x: left, y: top, w: right - left, h: bottom - top })
+    }
+}
+
+#[cfg(feature = "render")]
+impl From<Rect> for macroquad::math::Rect {
+    fn from(rect: Rect) -> Self {
+        macroquad::math::Rect::new(rect.x, rect.y, rect.w, rect.h)
+    }
+}
+
+// Uniform grid over axis-aligned rects, used to find the few rects that can
+// touch a given one without testing every pair. Each rect is stored in every
+// cell it overlaps; cells are `cell` metres square.
+pub struct Grid {
+    cell: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Grid {
+    pub fn new(rects: &[Rect], cell: f32) -> Self {
+        let mut grid = Grid { cell, cells: HashMap::new() };
+        for (index, rect) in rects.iter().enumerate() {
+            for key in grid.keys(*rect) {
+                grid.cells.entry(key).or_default().push(index);
+            }
+        }
+        grid
+    }
+
+    // Cells are inclusive of both edges so that rects which only touch, and
+    // therefore `intersect`, always share at least one cell
+    fn keys(&self, rect: Rect) -> impl Iterator<Item = (i32, i32)> {
+        let x0 = (rect.x / self.cell).floor() as i32;
+        let y0 = (rect.y / self.cell).floor() as i32;
+        let x1 = ((rect.x + rect.w) / self.cell).floor() as i32;
+        let y1 = ((rect.y + rect.h) / self.cell).floor() as i32;
+        (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+    }
+
+    // Indices of the stored rects sharing a cell with `rect`, ascending and
+    // without duplicates. `out` is reused to avoid allocating per query.
+    pub fn query(&self, rect: Rect, out: &mut Vec<usize>) {
+        out.clear();
+        for key in self.keys(rect) {
+            if let Some(indices) = self.cells.get(&key) {
+                out.extend_from_slice(indices);
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touching_rects_intersect_and_share_a_cell() {
+        let a = Rect::new(0.0, 0.0, 4.0, 4.0);
+        let b = Rect::new(4.0, 1.0, 2.0, 2.0);
+        assert_eq!(a.intersect(b), Some(Rect::new(4.0, 1.0, 0.0, 2.0)));
+        assert_eq!(a.intersect(Rect::new(4.1, 0.0, 1.0, 1.0)), None);
+
+        let grid = Grid::new(&[a, b, Rect::new(40.0, 40.0, 1.0, 1.0)], 4.0);
+        let mut found = Vec::new();
+        grid.query(a, &mut found);
+        assert_eq!(found, vec![0, 1]);
+    }
+
+    #[test]
+    fn contains_includes_only_the_top_left_edges() {
+        let rect = Rect::new(1.0, 1.0, 2.0, 2.0);
+        assert!(rect.contains((1.0, 1.0)));
+        assert!(!rect.contains((3.0, 2.0)));
+        assert!(!rect.contains((2.0, 3.0)));
+        assert_eq!(rect.combine_with(Rect::new(-1.0, 2.0, 1.0, 4.0)), Rect::new(-1.0, 1.0, 4.0, 5.0));
+        assert_eq!(rect.center(), (2.0, 2.0));
+    }
+}
@@ -0,0 +1,160 @@
+#[derive(Clone, Debug)]
+pub struct Stats {
+    pub total_cars: u32,
+    pub nbr_passed: u32,
+    pub max_velocity: f32,
+    pub min_velocity: f32,
+    pub average_velocity: f32,
+    pub max_time: f32,
+    pub min_time: f32,
+    pub total_velocity_sum: f64, // Velocity integrated over car-seconds
+    pub velocity_time: f64,      // Car-seconds sampled
+    pub total_energy: f64,
+    pub average_energy: f64,
+    pub max_energy: f64,
+    pub min_energy: f64,
+    pub total_distance: f32,
+    pub queued_cars: u32,      // Cars that had to wait in a spawn queue
+    pub total_queue_wait: f64, // Seconds spent in spawn queues before entering
+    pub max_queue_wait: f32,
+    pub total_delay: f64, // Seconds lost against driving the same distance at free-flow velocity
+    pub average_delay: f32,
+    pub pedestrians_crossed: u32,
+    pub total_pedestrian_wait: f64, // Seconds pedestrians waited at the curb for a gap
+    pub max_pedestrian_wait: f32,
+    pub average_pedestrian_wait: f32,
+    pub cyclists_passed: u32,
+    pub total_cyclist_delay: f64, // Seconds lost against riding straight through at their speed
+    pub max_cyclist_delay: f32,
+    pub average_cyclist_delay: f32,
+    pub cyclist_near_misses: u32, // Times a car's radar reached a cyclist
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            total_cars: 0,
+            nbr_passed: 0,
+            max_velocity: f32::MIN,
+            min_velocity: f32::MAX,
+            average_velocity: 0.0,
+            max_time: f32::MIN,
+            min_time: f32::MAX,
+            total_velocity_sum: 0.0,
+            velocity_time: 0.0,
+            total_energy: 0.0,
+            average_energy: 0.0,
+            max_energy: f64::MIN,
+            min_energy: f64::MAX,
+            total_distance: 0.0,
+            queued_cars: 0,
+            total_queue_wait: 0.0,
+            max_queue_wait: 0.0,
+            total_delay: 0.0,
+            average_delay: 0.0,
+            pedestrians_crossed: 0,
+            total_pedestrian_wait: 0.0,
+            max_pedestrian_wait: 0.0,
+            average_pedestrian_wait: 0.0,
+            cyclists_passed: 0,
+            total_cyclist_delay: 0.0,
+            max_cyclist_delay: 0.0,
+            average_cyclist_delay: 0.0,
+            cyclist_near_misses: 0,
+        }
+    }
+
+    // One car drove at `velocity` for `dt` seconds. Weighting by `dt` keeps
+    // the average independent of the tick length and time scale.
+    pub fn sample_velocity(&mut self, velocity: f32, dt: f32) {
+        if velocity > self.max_velocity { self.max_velocity = velocity; }
+        if velocity < self.min_velocity { self.min_velocity = velocity; }
+        self.total_velocity_sum += velocity as f64 * dt as f64;
+        self.velocity_time += dt as f64;
+        if self.velocity_time > 0.0 {
+            self.average_velocity = (self.total_velocity_sum / self.velocity_time) as f32;
+        }
+    }
+
+    // A pedestrian reached the far curb after waiting `wait` seconds
+    pub fn register_crossing(&mut self, wait: f32) {
+        tally(
+            wait,
+            &mut self.pedestrians_crossed,
+            &mut self.total_pedestrian_wait,
+            &mut self.max_pedestrian_wait,
+            &mut self.average_pedestrian_wait,
+        );
+    }
+
+    // A cyclist left the world `delay` seconds later than riding non-stop
+    pub fn register_cyclist(&mut self, delay: f32) {
+        tally(
+            delay,
+            &mut self.cyclists_passed,
+            &mut self.total_cyclist_delay,
+            &mut self.max_cyclist_delay,
+            &mut self.average_cyclist_delay,
+        );
+    }
+
+    pub fn register_cyclist_near_miss(&mut self) {
+        self.cyclist_near_misses += 1;
+    }
+
+    pub fn register_queue_wait(&mut self, wait: f32) {
+        self.queued_cars += 1;
+        self.total_queue_wait += wait as f64;
+        if wait > self.max_queue_wait { self.max_queue_wait = wait; }
+    }
+
+    // `time_lived` is measured on the simulation clock, so it excludes any
+    // time the simulation was paused. The delay is measured against driving
+    // the whole distance at `free_flow_velocity`.
+    pub fn register_passed_car(&mut self, time_lived: f32, energy: f64, distance: f32, free_flow_velocity: f32) {
+        self.nbr_passed += 1;
+        self.total_distance += distance;
+        if time_lived > self.max_time { self.max_time = time_lived; }
+        if time_lived < self.min_time { self.min_time = time_lived; }
+        if energy > self.max_energy { self.max_energy = energy; }
+        if energy < self.min_energy { self.min_energy = energy; }
+        self.total_energy += energy;
+        self.average_energy = self.total_energy / self.nbr_passed as f64;
+        // Includes the wait in the spawn queue, since the trip starts at the request
+        let delay = (time_lived - distance / free_flow_velocity).max(0.0);
+        self.total_delay += delay as f64;
+        self.average_delay = (self.total_delay / self.nbr_passed as f64) as f32;
+    }
+}
+
+// Counts one more wait or delay of `seconds` into its count, total, maximum
+// and average
+fn tally(seconds: f32, count: &mut u32, total: &mut f64, max: &mut f32, average: &mut f32) {
+    *count += 1;
+    *total += seconds as f64;
+    if seconds > *max { *max = seconds; }
+    *average = (*total / *count as f64) as f32;
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_velocity_is_weighted_by_time() {
+        let mut stats = Stats::new();
+        stats.sample_velocity(10.0, 3.0);
+        // Twelve quarter-second ticks count as much as one 3 s tick
+        for _ in 0..12 {
+            stats.sample_velocity(2.0, 0.25);
+        }
+        assert_eq!(stats.average_velocity, 6.0);
+        assert_eq!((stats.min_velocity, stats.max_velocity), (2.0, 10.0));
+    }
+}
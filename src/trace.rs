@@ -0,0 +1,197 @@
+use crate::cars::VehicleId;
+use crate::sim::Simulation;
+use std::fmt::Write;
+
+// Per-tick trajectories of every car, used for golden-trace regression tests.
+// The text format has one line per car per tick:
+//
+//     tick id direction x y velocity
+//
+// Cars are identified by their `VehicleId`, so a row keeps naming the same
+// car when an earlier one leaves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub tick: usize,
+    pub id: VehicleId,
+    pub direction: String,
+    pub cord: (f32, f32),
+    pub velocity: f32,
+}
+
+#[derive(Default)]
+pub struct Trace {
+    pub rows: Vec<Row>,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace { rows: Vec::new() }
+    }
+
+    pub fn record(&mut self, tick: usize, sim: &Simulation) {
+        for car in &sim.cars {
+            self.rows.push(Row {
+                tick,
+                id: car.id,
+                direction: car.direction.clone(),
+                cord: car.cord,
+                velocity: car.velocity,
+            });
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# tick id direction x y velocity\n");
+        for row in &self.rows {
+            let _ = writeln!(
+                text,
+                "{} {} {} {:.4} {:.4} {:.4}",
+                row.tick, row.id.0, row.direction, row.cord.0, row.cord.1, row.velocity
+            );
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Trace, String> {
+        let mut trace = Trace::new();
+        for (n, line) in text.lines().enumerate() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let bad = || format!("line {}: expected `tick id direction x y velocity`, got `{}`", n + 1, line);
+            if fields.len() != 6 {
+                return Err(bad());
+            }
+            let number = |k: usize| fields[k].parse::<f32>().map_err(|_| bad());
+            trace.rows.push(Row {
+                tick: fields[0].parse().map_err(|_| bad())?,
+                id: VehicleId(fields[1].parse().map_err(|_| bad())?),
+                direction: fields[2].to_string(),
+                cord: (number(3)?, number(4)?),
+                velocity: number(5)?,
+            });
+        }
+        Ok(trace)
+    }
+
+    // Compares against `expected` and describes the first divergence: the
+    // tick, the car and the field that moved by more than `tolerance`.
+    pub fn diff(&self, expected: &Trace, tolerance: f32) -> Option<String> {
+        for (got, want) in self.rows.iter().zip(&expected.rows) {
+            if got.tick != want.tick || got.id != want.id {
+                return Some(format!(
+                    "tick {}: expected car {} at this point of the trace, got tick {} car {} \
+                     (a car was added or removed earlier than before)",
+                    want.tick, want.id, got.tick, got.id
+                ));
+            }
+            let car = format!("tick {}, car {} ({})", want.tick, want.id, want.direction);
+            if got.direction != want.direction {
+                return Some(format!("{}: direction expected {}, got {}", car, want.direction, got.direction));
+            }
+            let fields = [
+                ("cord.x", want.cord.0, got.cord.0),
+                ("cord.y", want.cord.1, got.cord.1),
+                ("velocity", want.velocity, got.velocity),
+            ];
+            for (field, want, got) in fields {
+                if (want - got).abs() > tolerance {
+                    return Some(format!(
+                        "{}: {} expected {:.4}, got {:.4} (tolerance {})",
+                        car, field, want, got, tolerance
+                    ));
+                }
+            }
+        }
+        if self.rows.len() != expected.rows.len() {
+            return Some(format!(
+                "trace length differs: expected {} rows, got {} (last tick expected {}, got {})",
+                expected.rows.len(),
+                self.rows.len(),
+                expected.rows.last().map_or(0, |row| row.tick),
+                self.rows.last().map_or(0, |row| row.tick),
+            ));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cars::ROUTES;
+    use crate::sim::MAX_TICK;
+    use std::path::PathBuf;
+
+    const TOLERANCE: f32 = 1e-3;
+
+    // 24 seeded random requests 2/3 s apart, then run until the junction
+    // has cleared (capped at two minutes of simulated time)
+    fn seeded_scenario() -> Trace {
+        let mut sim = Simulation::with_seed(38);
+        let mut trace = Trace::new();
+        let mut tick = 0;
+        while tick < 120 * 60 {
+            if tick % 40 == 0 && tick < 24 * 40 {
+                sim.request_random(&ROUTES);
+            } else if sim.cars.is_empty() && sim.queued() == 0 {
+                break;
+            }
+            sim.tick(MAX_TICK);
+            trace.record(tick, &sim);
+            tick += 1;
+        }
+        trace
+    }
+
+    fn golden_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name)
+    }
+
+    // Fails on any behaviour change. After an intended change to `Car::update`
+    // or the controller, regenerate the file and commit it with the change:
+    //
+    //     UPDATE_GOLDEN=1 cargo test golden
+    #[test]
+    fn golden_seeded_scenario() {
+        let path = golden_path("seeded_scenario.trace");
+        let trace = seeded_scenario();
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, trace.to_text()).unwrap();
+            return;
+        }
+
+        let text = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!("missing golden file {}; run `UPDATE_GOLDEN=1 cargo test golden`", path.display())
+        });
+        let expected = Trace::parse(&text).unwrap();
+        // Compare what would be written, so rounding in the file does not count
+        let trace = Trace::parse(&trace.to_text()).unwrap();
+        if let Some(diff) = trace.diff(&expected, TOLERANCE) {
+            panic!("trace diverged from {}:\n{}\nIf intended, run `UPDATE_GOLDEN=1 cargo test golden`", path.display(), diff);
+        }
+    }
+
+    #[test]
+    fn diff_reports_first_diverging_field() {
+        let mut expected = Trace::new();
+        expected.rows.push(Row { tick: 4, id: VehicleId(1), direction: "up_left".to_string(), cord: (1.0, 2.0), velocity: 3.0 });
+        let mut got = Trace { rows: expected.rows.clone() };
+        assert_eq!(got.diff(&expected, TOLERANCE), None);
+
+        got.rows[0].velocity = 3.5;
+        let diff = got.diff(&expected, TOLERANCE).unwrap();
+        assert!(diff.contains("tick 4, car #1 (up_left): velocity"), "{}", diff);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let trace = seeded_scenario();
+        let parsed = Trace::parse(&trace.to_text()).unwrap();
+        assert_eq!(parsed.rows.len(), trace.rows.len());
+        assert_eq!(parsed.diff(&trace, TOLERANCE), None);
+    }
+}
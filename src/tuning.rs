@@ -0,0 +1,179 @@
+use crate::config::*;
+use crate::sim::Simulation;
+use crate::stats::Stats;
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets};
+use std::ops::Range;
+
+// Changes closer together than this count as one adjustment, e.g. the
+// frames of a slider drag, and share the same baseline
+const SETTLE_SECONDS: f64 = 1.0;
+
+const PANEL_SIZE: Vec2 = vec2(420.0, 740.0);
+
+// Statistics of a stretch of simulated time run with the same parameters
+pub struct Segment {
+    pub stats: Stats,
+    pub duration: f64,
+}
+
+impl Segment {
+    fn describe(&self) -> String {
+        let minutes = (self.duration / 60.0).max(1.0 / 60.0);
+        format!(
+            "{:.0} s: {} passed, {:.1} /min, delay {:.2} s",
+            self.duration,
+            self.stats.nbr_passed,
+            self.stats.nbr_passed as f64 / minutes,
+            self.stats.average_delay,
+        )
+    }
+}
+
+// Live tuning panel (T). Sliders edit a draft of the config and the spawn
+// demands; every valid change is applied to the running simulation at once
+// and forks the statistics: the current ones are kept as the baseline and
+// counting restarts, so the effect of the change can be compared directly.
+pub struct Tuning {
+    pub open: bool,
+    draft: Config,
+    demand: f32,
+    pedestrian_demand: f32,
+    cyclist_demand: f32,
+    error: Option<String>,
+    baseline: Option<Segment>,
+    segment_start: f64, // Simulation time the current parameters took effect
+    changed_at: f64,    // Wall time of the last change
+}
+
+impl Tuning {
+    pub fn new() -> Self {
+        Tuning {
+            open: false,
+            draft: Config::new(),
+            demand: 0.0,
+            pedestrian_demand: 0.0,
+            cyclist_demand: 0.0,
+            error: None,
+            baseline: None,
+            segment_start: 0.0,
+            changed_at: f64::NEG_INFINITY,
+        }
+    }
+
+    // Applies a config from elsewhere (the watched file) as if it had been
+    // set with the sliders
+    pub fn apply(&mut self, sim: &mut Simulation, config: Config) {
+        self.draft = config;
+        self.error = None;
+        if config != sim.config {
+            self.fork(sim);
+            sim.set_config(config);
+        }
+    }
+
+    // A fresh run (a control API reset) starts without a baseline; the
+    // config and the demands carry over, so the draft stays as it is
+    pub fn restart(&mut self, sim: &Simulation) {
+        self.baseline = None;
+        self.segment_start = sim.now();
+        self.changed_at = f64::NEG_INFINITY;
+    }
+
+    // Cars already on the road when the statistics restart are counted
+    // when they leave, under the new parameters
+    fn fork(&mut self, sim: &mut Simulation) {
+        if get_time() - self.changed_at > SETTLE_SECONDS {
+            self.baseline = Some(Segment { stats: sim.stats.clone(), duration: sim.now() - self.segment_start });
+        }
+        sim.stats = Stats::new();
+        self.segment_start = sim.now();
+        self.changed_at = get_time();
+    }
+
+    // True when the mouse is over the panel, so clicks there are not passed
+    // on to the world
+    pub fn wants_mouse(&self) -> bool {
+        self.open && root_ui().is_mouse_over(Vec2::from(mouse_position()))
+    }
+
+    pub fn draw(&mut self, sim: &mut Simulation) {
+        if !self.open {
+            return;
+        }
+        let position = vec2(screen_width() - PANEL_SIZE.x - 10.0, 40.0);
+        let mut reset = false;
+        widgets::Window::new(hash!(), position, PANEL_SIZE).label("Tuning").movable(true).ui(&mut root_ui(), |ui| {
+            let Config { controller: c, car, spawn, pedestrian, bike, human } = &mut self.draft;
+            let sliders: [(&str, &mut f32, Range<f32>); 19] = [
+                ("cruise km/h", &mut c.cruise_kmh, 20.0..200.0),
+                ("adjust km/h", &mut c.adjust_kmh, 10.0..200.0),
+                ("crawl km/h", &mut c.crawl_kmh, 5.0..100.0),
+                ("safety m", &mut c.safety_distance, 5.0..40.0),
+                ("yield radius m", &mut c.yield_radius, 10.0..50.0),
+                ("stop radius m", &mut c.stop_radius, 5.0..50.0),
+                ("tie margin m", &mut c.tie_margin, 0.0..10.0),
+                ("radar m", &mut car.radar_length, 2.0..30.0),
+                ("accel m/s2", &mut car.acceleration, 1.0..100.0),
+                ("braking m/s2", &mut car.deceleration, 1.0..300.0),
+                ("spawn gap m", &mut spawn.gap, 6.0..40.0),
+                ("spawns /min", &mut self.demand, 0.0..60.0),
+                ("walk m/s", &mut pedestrian.speed, 0.5..3.0),
+                ("pedestrians /min", &mut self.pedestrian_demand, 0.0..30.0),
+                ("bike km/h", &mut bike.speed_kmh, 5.0..40.0),
+                ("bike lane m", &mut bike.lane_width, 1.0..3.0),
+                ("bikes /min", &mut self.cyclist_demand, 0.0..30.0),
+                ("AV share", &mut human.penetration, 0.0..1.0),
+                ("human reaction s", &mut human.reaction_time, 0.0..3.0),
+            ];
+            for (k, (label, value, range)) in sliders.into_iter().enumerate() {
+                ui.slider(hash!("tuning", k), label, range, value);
+            }
+
+            ui.separator();
+            reset = ui.button(None, "Defaults");
+            if let Some(error) = &self.error {
+                ui.label(None, error);
+            }
+            ui.separator();
+            ui.label(None, "Since last change");
+            ui.label(None, &Segment { stats: sim.stats.clone(), duration: sim.now() - self.segment_start }.describe());
+            if let Some(baseline) = &self.baseline {
+                ui.label(None, "Before");
+                ui.label(None, &baseline.describe());
+            }
+        });
+
+        if reset {
+            self.draft = Config::new();
+        }
+        if self.demand as f64 != sim.demand {
+            self.fork(sim);
+            sim.demand = self.demand as f64;
+        }
+        if self.pedestrian_demand as f64 != sim.pedestrian_demand {
+            self.fork(sim);
+            sim.pedestrian_demand = self.pedestrian_demand as f64;
+        }
+        if self.cyclist_demand as f64 != sim.cyclist_demand {
+            self.fork(sim);
+            sim.cyclist_demand = self.cyclist_demand as f64;
+        }
+        if self.draft != sim.config {
+            // Invalid combinations (e.g. crawl above cruise) are shown and
+            // not applied until they are fixed
+            match self.draft.validate() {
+                Ok(()) => self.apply(sim, self.draft),
+                Err(err) => self.error = Some(err.to_string()),
+            }
+        } else {
+            self.error = None;
+        }
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,45 @@
+// World coordinate system. Positions, sizes and distances are in metres and
+// velocities in m/s; pixels only appear when the world is drawn through the
+// camera of `camera::View`.
+//
+// The world is larger than the window so that each approach road is about
+// 100 m longer than what fits on screen at the default scale; queues can build
+// up off-screen and are reached by panning or zooming out.
+use crate::spatial::Rect;
+
+pub const WORLD_WIDTH: f32 = 310.0;
+pub const WORLD_HEIGHT: f32 = 280.0;
+pub const PIXELS_PER_METRE: f32 = 10.0;
+
+// Area around the intersection shown when the view is reset, whatever the
+// window size; the initial window shows it at `PIXELS_PER_METRE`.
+pub const VIEW_WIDTH: f32 = 110.0;
+pub const VIEW_HEIGHT: f32 = 80.0;
+
+pub const WINDOW_WIDTH: i32 = (VIEW_WIDTH * PIXELS_PER_METRE) as i32;
+pub const WINDOW_HEIGHT: i32 = (VIEW_HEIGHT * PIXELS_PER_METRE) as i32;
+
+pub const CAR_WIDTH: f32 = 3.0;
+pub const CAR_LENGTH: f32 = 5.0;
+
+// Half the side of the intersection box, between the stop lines
+pub const BOX_HALF_WIDTH: f32 = 12.0;
+
+// Centre of the intersection in world coordinates
+pub fn centre() -> (f32, f32) {
+    (WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0)
+}
+
+// The intersection box, where the crossing routes conflict
+pub fn intersection_box() -> Rect {
+    let (cx, cy) = centre();
+    Rect::new(cx - BOX_HALF_WIDTH, cy - BOX_HALF_WIDTH, 2.0 * BOX_HALF_WIDTH, 2.0 * BOX_HALF_WIDTH)
+}
+
+pub const fn kmh(v: f32) -> f32 {
+    v / 3.6
+}
+
+pub const fn to_kmh(v: f32) -> f32 {
+    v * 3.6
+}